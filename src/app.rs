@@ -3,26 +3,38 @@ use std::borrow::ToOwned;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, stderr, stdin, stdout, Write};
 use std::iter::Iterator;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
+use std::time::Duration;
 
 use app_dirs::{AppInfo, AppDataType, get_app_dir};
 use clap::ArgMatches;
 use if_let_return::if_let_some;
 use indicatif::{ProgressBar, ProgressStyle};
 
+use crate::archive::{self, ConflictPolicy, PrefixRewrite};
 use crate::args;
-use crate::database::Database;
-use crate::server::download::Manager;
-use crate::errors::{AppResult, AppResultU, from_path};
+use crate::collection;
+use crate::database::{CASE_FOLDING, Database, UNICODE_NORMALIZATION};
+use crate::server::download::{self, Manager};
+use crate::server::hook::Conversion;
+use crate::server::listen::Listen;
+use crate::errors::{AppError, AppResult, AppResultU, from_path};
+use crate::event::EventBus;
 use crate::expander::Expander;
+use crate::federation::{self, Attached, FederatedMeta};
 use crate::global_alias::GlobalAliasTable;
+use crate::implication::Implications;
 use crate::loader::Config;
 use crate::loader;
 use crate::output_format::OutputFormat;
+use crate::saved_search::SavedSearch;
+use crate::search_history::{self, HistoryFilter};
 use crate::server::start as start_server;
-use crate::tag::Tag;
+use crate::server;
+use crate::tag::{Tag, TagOperation};
+use crate::token::{Scope, generate_secret, join_scopes};
 
 
 
@@ -34,10 +46,7 @@ pub fn run(matches: &ArgMatches) -> AppResultU {
         if let Some(path) = matches.value_of("database-path") {
             Path::new(path).to_owned()
         } else {
-            let mut path = get_app_dir(AppDataType::UserData, &APP_INFO, "db")?;
-            let name = matches.value_of("database-name").unwrap_or("default");
-            path.push(format!("{}.sqlite", name));
-            path
+            database_file(matches.value_of("database-name").unwrap_or("default"))?
         }
     };
     let aliases_file = {
@@ -47,7 +56,17 @@ pub fn run(matches: &ArgMatches) -> AppResultU {
             get_app_dir(AppDataType::UserConfig, &APP_INFO, "aliases.yaml").unwrap()
         }
     };
+    if let Some(matches) = matches.subcommand_matches("db") {
+        // Before `Database::open` which applies the migrations
+        if let Some(matches) = matches.subcommand_matches("migrate") {
+            let dry_run = matches.is_present("dry-run");
+            return command_db_migrate(&db_file, dry_run);
+        }
+        eprintln!("{}", matches.usage());
+        exit(1);
+    }
     let db = Database::open(&db_file)?;
+    let attached = attached_databases(matches)?;
     let mut aliases = GlobalAliasTable::open(&aliases_file)?;
 
     if let Some(matches) = matches.subcommand_matches("alias") {
@@ -56,6 +75,38 @@ pub fn run(matches: &ArgMatches) -> AppResultU {
         let recursive = matches.is_present("recursive");
         let local = matches.is_present("local");
         command_alias(&db, aliases, name, expressions, recursive, local)?;
+    } else if let Some(matches) = matches.subcommand_matches("collection") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let name: &str = matches.value_of("name").unwrap();
+            let paths: Vec<&str> = matches.values_of("path").map(Iterator::collect).unwrap_or_default();
+            let at: Option<usize> = matches.value_of("at").map(str::parse).transpose()?;
+            command_collection_add(&db, name, &paths, at)?;
+        } else if let Some(matches) = matches.subcommand_matches("create") {
+            let name: &str = matches.value_of("name").unwrap();
+            command_collection_create(&db, name)?;
+        } else if let Some(matches) = matches.subcommand_matches("delete") {
+            let name: &str = matches.value_of("name").unwrap();
+            command_collection_delete(&db, name)?;
+        } else if let Some(matches) = matches.subcommand_matches("export") {
+            let name: &str = matches.value_of("name").unwrap();
+            let format = matches.value_of("format").map(OutputFormat::from_str).unwrap_or(Ok(OutputFormat::Simple))?;
+            command_collection_export(&db, name, format)?;
+        } else if let Some(matches) = matches.subcommand_matches("list") {
+            let name: Option<&str> = matches.value_of("name");
+            command_collection_list(&db, name)?;
+        } else if let Some(matches) = matches.subcommand_matches("move") {
+            let name: &str = matches.value_of("name").unwrap();
+            let path: &str = matches.value_of("path").unwrap();
+            let position: usize = matches.value_of("position").unwrap().parse()?;
+            command_collection_move(&db, name, path, position)?;
+        } else if let Some(matches) = matches.subcommand_matches("remove") {
+            let name: &str = matches.value_of("name").unwrap();
+            let paths: Vec<&str> = matches.values_of("path").map(Iterator::collect).unwrap_or_default();
+            command_collection_remove(&db, name, &paths)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
     } else if let Some(matches) = matches.subcommand_matches("completions") {
         let shell = matches.value_of("shell").unwrap();
         args::build_cli().gen_completions_to("noir", shell.parse().unwrap(), &mut stdout());
@@ -68,12 +119,65 @@ pub fn run(matches: &ArgMatches) -> AppResultU {
         let expression = matches.value_of("expression").unwrap();
         let full = matches.is_present("full");
         command_expand(&db, aliases, expression, full)?;
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        let output: Option<&str> = matches.value_of("output");
+        command_export(&db, output)?;
     } else if let Some(matches) = matches.subcommand_matches("get") {
         let path = matches.value_of("path").unwrap();
         let format = matches.value_of("format").map(OutputFormat::from_str).unwrap_or(Ok(OutputFormat::Simple))?;
         command_get(&db, path, format)?;
-    } else if matches.subcommand_matches("history").is_some() {
-        command_history(&db)?;
+    } else if let Some(matches) = matches.subcommand_matches("history") {
+        if let Some(matches) = matches.subcommand_matches("clear") {
+            let older_than: Option<&str> = matches.value_of("older-than");
+            command_history_clear(&db, older_than)?;
+        } else if let Some(matches) = matches.subcommand_matches("delete") {
+            let expression: &str = matches.value_of("expression").unwrap();
+            command_history_delete(&db, expression)?;
+        } else if let Some(matches) = matches.subcommand_matches("list") {
+            let filter = HistoryFilter {
+                q: matches.value_of("filter").map(str::to_owned),
+                pinned: if matches.is_present("pinned") { Some(true) } else { None },
+                limit: matches.value_of("limit").map(str::parse).transpose()?,
+            };
+            command_history_list(&db, &filter)?;
+        } else if let Some(matches) = matches.subcommand_matches("max-size") {
+            let size: Option<u64> = matches.value_of("size").map(str::parse).transpose()?;
+            command_history_max_size(&db, size)?;
+        } else if let Some(matches) = matches.subcommand_matches("pin") {
+            let expression: &str = matches.value_of("expression").unwrap();
+            command_history_pin(&db, expression, true)?;
+        } else if let Some(matches) = matches.subcommand_matches("unpin") {
+            let expression: &str = matches.value_of("expression").unwrap();
+            command_history_pin(&db, expression, false)?;
+        } else {
+            command_history(&db)?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("implication") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let child: &str = matches.value_of("child").unwrap();
+            let parent: &str = matches.value_of("parent").unwrap();
+            command_implication_add(&db, child, parent)?;
+        } else if let Some(matches) = matches.subcommand_matches("apply") {
+            let source: &str = matches.value_of("source").unwrap_or("implication");
+            command_implication_apply(&db, source)?;
+        } else if matches.subcommand_matches("list").is_some() {
+            command_implication_list(&db)?;
+        } else if let Some(matches) = matches.subcommand_matches("remove") {
+            let child: &str = matches.value_of("child").unwrap();
+            let parent: &str = matches.value_of("parent").unwrap();
+            command_implication_remove(&db, child, parent)?;
+        } else if let Some(matches) = matches.subcommand_matches("show") {
+            let tag: &str = matches.value_of("tag").unwrap();
+            command_implication_show(&db, tag)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("import") {
+        let input: Option<&str> = matches.value_of("input");
+        let policy: ConflictPolicy = matches.value_of("conflict").unwrap_or("skip").parse()?;
+        let rewrite: Option<PrefixRewrite> = matches.value_of("rewrite").map(str::parse).transpose()?;
+        command_import(&db, input, policy, rewrite.as_ref())?;
     } else if let Some(matches) = matches.subcommand_matches("load") {
         let paths: Vec<&str> = matches.values_of("path").unwrap().collect();
         command_load(&db, &paths, extract_loader_config(matches))?;
@@ -86,38 +190,175 @@ pub fn run(matches: &ArgMatches) -> AppResultU {
         command_meta(path, format)?;
     } else if matches.is_present("path") {
         println!("{}", from_path(&db_file)?);
+    } else if let Some(matches) = matches.subcommand_matches("rating") {
+        if let Some(matches) = matches.subcommand_matches("clear") {
+            let path: &str = matches.value_of("path").unwrap();
+            command_rating_set(&db, path, None)?;
+        } else if let Some(matches) = matches.subcommand_matches("set") {
+            let path: &str = matches.value_of("path").unwrap();
+            let rating: u8 = matches.value_of("rating").unwrap().parse()?;
+            command_rating_set(&db, path, Some(rating))?;
+        } else if let Some(matches) = matches.subcommand_matches("show") {
+            let path: &str = matches.value_of("path").unwrap();
+            command_rating_show(&db, path)?;
+        } else if let Some(matches) = matches.subcommand_matches("view") {
+            let path: &str = matches.value_of("path").unwrap();
+            command_rating_view(&db, path)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("relocate") {
+        let rewrite = PrefixRewrite {
+            from: matches.value_of("old-prefix").unwrap().to_owned(),
+            to: matches.value_of("new-prefix").unwrap().to_owned(),
+        };
+        let sample: usize = matches.value_of("sample").unwrap_or("10").parse()?;
+        command_relocate(&db, &rewrite, sample)?;
     } else if matches.is_present("reset") {
         command_reset(&db)?;
+    } else if let Some(matches) = matches.subcommand_matches("root") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let name: &str = matches.value_of("name").unwrap();
+            let path: &str = matches.value_of("path").unwrap();
+            command_root_add(&db, name, path)?;
+        } else if matches.subcommand_matches("list").is_some() {
+            command_root_list(&db)?;
+        } else if let Some(matches) = matches.subcommand_matches("move") {
+            let name: &str = matches.value_of("name").unwrap();
+            let path: &str = matches.value_of("path").unwrap();
+            let sample: usize = matches.value_of("sample").unwrap_or("10").parse()?;
+            command_root_move(&db, name, path, sample)?;
+        } else if let Some(matches) = matches.subcommand_matches("remove") {
+            let name: &str = matches.value_of("name").unwrap();
+            command_root_remove(&db, name)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("saved") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let expressions: Vec<&str> = matches.values_of("expression").unwrap().collect();
+            let saved = SavedSearch {
+                name: matches.value_of("name").unwrap().to_owned(),
+                expression: join(&expressions),
+                description: matches.value_of("description").map(ToOwned::to_owned),
+                sort: matches.value_of("sort").map(ToOwned::to_owned),
+                limit: matches.value_of("limit").map(str::parse).transpose()?,
+                ..Default::default()
+            };
+            command_saved_add(&db, &aliases, &saved)?;
+        } else if matches.subcommand_matches("list").is_some() {
+            command_saved_list(&db)?;
+        } else if let Some(matches) = matches.subcommand_matches("refresh") {
+            let name: Option<&str> = matches.value_of("name");
+            command_saved_refresh(&db, &aliases, name)?;
+        } else if let Some(matches) = matches.subcommand_matches("remove") {
+            let name: &str = matches.value_of("name").unwrap();
+            command_saved_remove(&db, name)?;
+        } else if let Some(matches) = matches.subcommand_matches("search") {
+            let name: &str = matches.value_of("name").unwrap();
+            let format = matches.value_of("format").map(OutputFormat::from_str).unwrap_or(Ok(OutputFormat::Simple))?;
+            command_saved_search(&db, &aliases, name, format)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
     } else if let Some(matches) = matches.subcommand_matches("search") {
         let wheres: Vec<&str> = matches.values_of("where").unwrap().collect();
         let vacuum = matches.is_present("vacuum");
         let format = matches.value_of("format").map(OutputFormat::from_str).unwrap_or(Ok(OutputFormat::Simple))?;
-        command_search(&db, aliases, &join(&wheres), vacuum, format)?;
+        if attached.is_empty() {
+            command_search(&db, aliases, &join(&wheres), vacuum, format)?;
+        } else if vacuum {
+            return Err(AppError::Standard("`--vacuum` can not be used with `--attach`"));
+        } else {
+            command_search_federated(&db, &attached, aliases, &join(&wheres), format)?;
+        }
     } else if let Some(matches) = matches.subcommand_matches("server") {
-        let port: u16 = matches.value_of("port").unwrap_or("9696").parse()?;
-        let root: &str = matches.value_of("root").unwrap_or("static");
-        let download_to: Option<&str> = matches.value_of("download-to");
-        return command_server(db, &db_file, aliases, port, root, download_to);
+        let config = extract_server_config(matches)?;
+        let dl_config = extract_download_config(matches)?;
+        return command_server(db, &db_file, attached, aliases, config, dl_config);
+    } else if let Some(matches) = matches.subcommand_matches("source") {
+        if matches.subcommand_matches("list").is_some() {
+            command_source_list(&db)?;
+        } else if let Some(matches) = matches.subcommand_matches("priority") {
+            let source: &str = matches.value_of("source").unwrap();
+            let priority: i64 = matches.value_of("priority").unwrap().parse()?;
+            command_source_priority(&db, source, priority)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("synonym") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let alias: &str = matches.value_of("alias").unwrap();
+            let canonical: &str = matches.value_of("canonical").unwrap();
+            command_synonym_add(&db, alias, canonical)?;
+        } else if let Some(matches) = matches.subcommand_matches("config") {
+            let case_folding: Option<bool> = matches.value_of("case-folding").map(|it| it == "on");
+            let unicode: Option<bool> = matches.value_of("unicode").map(|it| it == "on");
+            command_synonym_config(&db, case_folding, unicode)?;
+        } else if matches.subcommand_matches("list").is_some() {
+            command_synonym_list(&db)?;
+        } else if let Some(matches) = matches.subcommand_matches("remove") {
+            let alias: &str = matches.value_of("alias").unwrap();
+            command_synonym_remove(&db, alias)?;
+        } else if matches.subcommand_matches("renormalize").is_some() {
+            command_synonym_renormalize(&db)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
     } else if let Some(matches) = matches.subcommand_matches("tag") {
         if let Some(matches) = matches.subcommand_matches("add") {
             let path: &str = matches.value_of("path").unwrap();
             let tags: Vec<&str> = matches.values_of("tag").map(Iterator::collect).unwrap_or_default();
             let source: &str = matches.value_of("source").unwrap();
             command_tag_add(&db, path, &tags, source)?;
+        } else if let Some(matches) = matches.subcommand_matches("batch") {
+            let operation = TagOperation::from_str(matches.value_of("operation").unwrap())?;
+            let source: &str = matches.value_of("source").unwrap();
+            let tags: Vec<&str> = matches.values_of("tag").map(Iterator::collect).unwrap_or_default();
+            let paths: Vec<&str> = matches.values_of("path").map(Iterator::collect).unwrap_or_default();
+            let paths = target_paths(&db, &aliases, &paths, matches.value_of("where"))?;
+            let dry_run = matches.is_present("dry-run");
+            command_tag_batch(&db, operation, source, &tags, &paths, dry_run)?;
         } else if let Some(matches) = matches.subcommand_matches("clear") {
             let path: &str = matches.value_of("path").unwrap();
             let source: &str = matches.value_of("source").unwrap();
             command_tag_clear(&db, path, source)?;
+        } else if let Some(matches) = matches.subcommand_matches("drop") {
+            let tag: &str = matches.value_of("tag").unwrap();
+            let source: Option<&str> = matches.value_of("source");
+            command_tag_drop(&db, tag, source)?;
+        } else if let Some(matches) = matches.subcommand_matches("merge") {
+            let tags: Vec<&str> = matches.values_of("tag").map(Iterator::collect).unwrap_or_default();
+            let into: &str = matches.value_of("into").unwrap();
+            let source: Option<&str> = matches.value_of("source");
+            command_tag_merge(&db, &tags, into, source)?;
+        } else if matches.subcommand_matches("namespaces").is_some() {
+            command_tag_namespaces(&db)?;
         } else if let Some(matches) = matches.subcommand_matches("remove") {
             let path: &str = matches.value_of("path").unwrap();
             let tags: Vec<&str> = matches.values_of("tag").map(Iterator::collect).unwrap_or_default();
             let source: &str = matches.value_of("source").unwrap();
             command_tag_remove(&db, path, &tags, source)?;
+        } else if let Some(matches) = matches.subcommand_matches("rename") {
+            let old: &str = matches.value_of("old").unwrap();
+            let new: &str = matches.value_of("new").unwrap();
+            let source: Option<&str> = matches.value_of("source");
+            command_tag_rename(&db, old, new, source)?;
         } else if let Some(matches) = matches.subcommand_matches("set") {
             let path: &str = matches.value_of("path").unwrap();
             let tags: Vec<&str> = matches.values_of("tag").map(Iterator::collect).unwrap_or_default();
             let source: &str = matches.value_of("source").unwrap();
             command_tag_set(&db, path, &tags, source)?;
+        } else if let Some(matches) = matches.subcommand_matches("stats") {
+            let source: Option<&str> = matches.value_of("source");
+            let with: Option<&str> = matches.value_of("with");
+            let limit: Option<usize> = matches.value_of("limit").map(str::parse).transpose()?;
+            command_tag_stats(&db, source, with, limit)?;
         } else if let Some(matches) = matches.subcommand_matches("show") {
             let path: Option<&str> = matches.value_of("path");
             command_tag_show(&db, path)?;
@@ -125,6 +366,20 @@ pub fn run(matches: &ArgMatches) -> AppResultU {
             eprintln!("{}", matches.usage());
             exit(1);
         }
+    } else if let Some(matches) = matches.subcommand_matches("token") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            let name: &str = matches.value_of("name").unwrap();
+            let scopes: Vec<&str> = matches.values_of("scope").map(Iterator::collect).unwrap_or_default();
+            command_token_add(&db, name, &scopes)?;
+        } else if matches.subcommand_matches("list").is_some() {
+            command_token_list(&db)?;
+        } else if let Some(matches) = matches.subcommand_matches("revoke") {
+            let name: &str = matches.value_of("name").unwrap();
+            command_token_revoke(&db, name)?;
+        } else {
+            eprintln!("{}", matches.usage());
+            exit(1);
+        }
     } else if let Some(matches) = matches.subcommand_matches("unalias") {
         let name = matches.value_of("name").unwrap();
         let local = matches.is_present("local");
@@ -164,6 +419,62 @@ fn command_alias(db: &Database, mut aliases: GlobalAliasTable, name: Option<&str
     Ok(())
 }
 
+fn command_collection_add(db: &Database, name: &str, paths: &[&str], at: Option<usize>) -> AppResultU {
+    let paths = canonical_paths(paths)?;
+    let mut items = db.collection_paths(name)?;
+    collection::insert(&mut items, &paths, at);
+    db.set_collection_paths(name, &items)
+}
+
+fn command_collection_create(db: &Database, name: &str) -> AppResultU {
+    db.create_collection(name)
+}
+
+fn command_collection_delete(db: &Database, name: &str) -> AppResultU {
+    if !db.delete_collection(name)? {
+        eprintln!("Collection not found: {}", name);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn command_collection_export(db: &Database, name: &str, format: OutputFormat) -> AppResultU {
+    let out = stdout();
+    let out = out.lock();
+    let mut out = BufWriter::new(out);
+    db.select_collection(name, |meta| format.write(&mut out, meta))
+}
+
+fn command_collection_list(db: &Database, name: Option<&str>) -> AppResultU {
+    if let Some(name) = name {
+        for path in db.collection_paths(name)? {
+            println!("{}", path);
+        }
+    } else {
+        for it in db.collections()? {
+            println!("{}\t{}", it.items, it.name);
+        }
+    }
+    Ok(())
+}
+
+fn command_collection_move(db: &Database, name: &str, path: &str, position: usize) -> AppResultU {
+    let path = Path::new(path).canonicalize()?;
+    let path = from_path(&path)?;
+    let mut items = db.collection_paths(name)?;
+    if !collection::move_item(&mut items, path, position) {
+        return Err(AppError::PathNotFound(path.to_owned()));
+    }
+    db.set_collection_paths(name, &items)
+}
+
+fn command_collection_remove(db: &Database, name: &str, paths: &[&str]) -> AppResultU {
+    let paths = canonical_paths(paths)?;
+    let mut items = db.collection_paths(name)?;
+    collection::remove(&mut items, &paths);
+    db.set_collection_paths(name, &items)
+}
+
 fn command_compute(db: &Database, aliases: GlobalAliasTable, expression: &str, format: OutputFormat, chunk_size: usize) -> AppResultU {
     let error = stderr();
     let error = error.lock();
@@ -177,8 +488,10 @@ fn command_compute(db: &Database, aliases: GlobalAliasTable, expression: &str, f
     let expanded_expression = expander.expand_str(expression)?;
 
     let mut entries = vec![];
+    let mut results = 0;
 
     db.select(expanded_expression.as_ref(), false, |meta, _vacuumed| {
+        results += 1;
         if meta.dhash.is_none() {
             entries.push(meta.clone());
         }
@@ -207,7 +520,21 @@ fn command_compute(db: &Database, aliases: GlobalAliasTable, expression: &str, f
         }
     }
 
-    db.add_search_history(expression)
+    db.add_search_history(expression, Some(results))
+}
+
+fn command_db_migrate<T: AsRef<Path>>(db_file: &T, dry_run: bool) -> AppResultU {
+    let pending = Database::pending_migrations(db_file)?;
+    for it in &pending {
+        let destructive = if it.destructive { " (destructive)" } else { "" };
+        println!("v{}\t{}{}", it.version, it.description, destructive);
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let db = Database::migrate_and_open(db_file)?;
+    println!("Schema version: {}", db.schema_version()?);
+    Ok(())
 }
 
 fn command_expand(db: &Database, aliases: GlobalAliasTable, expression: &str, full: bool) -> AppResultU {
@@ -221,6 +548,20 @@ fn command_expand(db: &Database, aliases: GlobalAliasTable, expression: &str, fu
     Ok(())
 }
 
+fn command_export(db: &Database, output: Option<&str>) -> AppResultU {
+    if let Some(output) = output {
+        let mut out = BufWriter::new(File::create(output)?);
+        archive::export(db, &mut out)?;
+        out.flush()?;
+    } else {
+        let out = stdout();
+        let mut out = BufWriter::new(out.lock());
+        archive::export(db, &mut out)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
 fn command_get(db: &Database, path: &str, format: OutputFormat) -> AppResultU {
     if let Some(meta) = db.get(path)? {
         let output = stdout();
@@ -234,12 +575,49 @@ fn command_get(db: &Database, path: &str, format: OutputFormat) -> AppResultU {
 }
 
 fn command_history(db: &Database) -> AppResultU {
-    for entry in db.search_history()? {
+    for entry in db.search_history(&HistoryFilter::default())? {
         println!("{}", entry.expression);
     }
     Ok(())
 }
 
+fn command_history_clear(db: &Database, older_than: Option<&str>) -> AppResultU {
+    let before = older_than.map(search_history::older_than).transpose()?;
+    let deleted = db.clear_search_history(before)?;
+    println!("{} entries deleted", deleted);
+    Ok(())
+}
+
+fn command_history_delete(db: &Database, expression: &str) -> AppResultU {
+    db.delete_search_history(expression)
+}
+
+fn command_history_list(db: &Database, filter: &HistoryFilter) -> AppResultU {
+    for entry in db.search_history(filter)? {
+        let results = entry.results.map(|it| it.to_string()).unwrap_or_else(|| "-".to_owned());
+        let last_used = entry.last_used.map(|it| it.to_rfc3339()).unwrap_or_else(|| "-".to_owned());
+        let pinned = if entry.pinned { "*" } else { " " };
+        println!("{}\t{}\t{}\t{}\t{}", pinned, entry.uses, results, last_used, entry.expression);
+    }
+    Ok(())
+}
+
+fn command_history_max_size(db: &Database, size: Option<u64>) -> AppResultU {
+    if let Some(size) = size {
+        let deleted = db.set_history_max_size(size)?;
+        if 0 < deleted {
+            println!("{} entries deleted", deleted);
+        }
+    } else {
+        println!("{}", db.history_max_size()?);
+    }
+    Ok(())
+}
+
+fn command_history_pin(db: &Database, expression: &str, pinned: bool) -> AppResultU {
+    db.set_history_pinned(expression, pinned)
+}
+
 fn command_meta(path: &str, format: OutputFormat) -> AppResultU {
     use crate::meta::Meta;
     let meta = Meta::from_file(&path, true)?;
@@ -249,6 +627,57 @@ fn command_meta(path: &str, format: OutputFormat) -> AppResultU {
     Ok(())
 }
 
+fn command_implication_add(db: &Database, child: &str, parent: &str) -> AppResultU {
+    let child = Tag::from_str(child)?;
+    let parent = Tag::from_str(parent)?;
+    db.add_implication(&child, &parent)
+}
+
+fn command_implication_apply(db: &Database, source: &str) -> AppResultU {
+    let _tx = db.transaction()?;
+    let added = db.apply_implications(source)?;
+    println!("Added {} rows", added);
+    Ok(())
+}
+
+fn command_implication_list(db: &Database) -> AppResultU {
+    for it in db.implications()? {
+        println!("{}\t{}", it.child, it.parent);
+    }
+    Ok(())
+}
+
+fn command_implication_remove(db: &Database, child: &str, parent: &str) -> AppResultU {
+    if !db.delete_implication(child, parent)? {
+        eprintln!("Implication not found: {} -> {}", child, parent);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn command_implication_show(db: &Database, tag: &str) -> AppResultU {
+    let implications: Implications = db.implications()?.iter().collect();
+    for it in implications.ancestors(tag) {
+        if it != tag {
+            println!("{}", it);
+        }
+    }
+    Ok(())
+}
+
+fn command_import(db: &Database, input: Option<&str>, policy: ConflictPolicy, rewrite: Option<&PrefixRewrite>) -> AppResultU {
+    let result = if let Some(input) = input {
+        archive::import(db, BufReader::new(File::open(input)?), policy, rewrite)?
+    } else {
+        let input = stdin();
+        archive::import(db, input.lock(), policy, rewrite)?
+    };
+    println!(
+        "images={} aliases={} history={} collections={} skipped={}",
+        result.images, result.aliases, result.history, result.collections, result.skipped);
+    Ok(())
+}
+
 fn command_load(db: &Database, paths: &[&str], config: Config) -> AppResultU {
     let _tx = db.transaction()?;
     let mut loader = loader::Loader::new(db, config);
@@ -277,6 +706,78 @@ fn command_load_list(db: &Database, mut paths: &[&str], config: Config) -> AppRe
     Ok(())
 }
 
+fn command_root_add(db: &Database, name: &str, path: &str) -> AppResultU {
+    let path = Path::new(path).canonicalize()?;
+    db.add_root(name, from_path(&path)?)
+}
+
+fn command_root_list(db: &Database) -> AppResultU {
+    for root in db.roots()? {
+        println!("{}\t{}\t{}\t{}", root.name, root.images, root.size, root.path);
+    }
+    Ok(())
+}
+
+fn command_root_move(db: &Database, name: &str, path: &str, sample: usize) -> AppResultU {
+    let path = Path::new(path).canonicalize()?;
+    let relocated = db.move_root(name, from_path(&path)?, sample)?;
+    println!("{} images relocated", relocated);
+    Ok(())
+}
+
+fn command_root_remove(db: &Database, name: &str) -> AppResultU {
+    if !db.delete_root(name)? {
+        return Err(AppError::RootNotFound(name.to_owned()));
+    }
+    Ok(())
+}
+
+fn command_saved_add(db: &Database, aliases: &GlobalAliasTable, saved: &SavedSearch) -> AppResultU {
+    let expander = Expander::generate(db, aliases)?;
+    expander.expand_str(&saved.expression)?;
+    db.upsert_saved_search(saved)
+}
+
+fn command_saved_list(db: &Database) -> AppResultU {
+    let mut saved: Vec<SavedSearch> = db.saved_searches()?.into_values().collect();
+    saved.sort_by(|a, b| a.name.cmp(&b.name));
+    for it in saved {
+        let count = it.count.map(|it| it.to_string()).unwrap_or_else(|| "-".to_owned());
+        println!("{}\t{}\t{}", it.name, count, it.expression);
+    }
+    Ok(())
+}
+
+fn command_saved_refresh(db: &Database, aliases: &GlobalAliasTable, name: Option<&str>) -> AppResultU {
+    let expander = Expander::generate(db, aliases)?;
+    let names: Vec<String> = match name {
+        Some(name) => vec![name.to_owned()],
+        None => db.saved_searches()?.into_keys().collect(),
+    };
+    for name in names {
+        let count = db.select_paths(&expander.expand_saved_search(&name)?)?.len() as u64;
+        db.set_saved_search_count(&name, count)?;
+        println!("{}\t{}", name, count);
+    }
+    Ok(())
+}
+
+fn command_saved_remove(db: &Database, name: &str) -> AppResultU {
+    if !db.delete_saved_search(name)? {
+        eprintln!("Saved search not found: {}", name);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn command_saved_search(db: &Database, aliases: &GlobalAliasTable, name: &str, format: OutputFormat) -> AppResultU {
+    let expander = Expander::generate(db, aliases)?;
+    let out = stdout();
+    let out = out.lock();
+    let mut out = BufWriter::new(out);
+    db.select(&expander.expand_saved_search(name)?, false, |meta, _vacuumed| format.write(&mut out, meta))
+}
+
 fn command_search(db: &Database, aliases: GlobalAliasTable, expression: &str, vacuum: bool, format: OutputFormat) -> AppResultU {
     let error = stderr();
     let error = error.lock();
@@ -289,21 +790,96 @@ fn command_search(db: &Database, aliases: GlobalAliasTable, expression: &str, va
     let expander = Expander::generate(db, &aliases)?;
     let expanded_expression = expander.expand_str(expression)?;
 
+    let mut results = 0;
     db.select(expanded_expression.as_ref(), vacuum, |meta, vacuumed| {
         if vacuumed {
             writeln!(error, "Vacuumed: {}", meta.file.path)?;
         } else {
+            results += 1;
             format.write(&mut output, meta)?;
         }
         Ok(())
     })?;
 
-    db.add_search_history(expression)
+    db.add_search_history(expression, Some(results))
 }
 
-fn command_server<T: AsRef<Path>>(db: Database, db_file: &T, aliases: GlobalAliasTable, port: u16, root: &str, download_to: Option<&str>) -> AppResultU {
-    let manager = Manager::new(Database::open(db_file)?);
-    start_server(db, manager, aliases, port, root.to_owned(), download_to.map(ToOwned::to_owned))?;
+fn command_search_federated(db: &Database, attached: &[Attached], aliases: GlobalAliasTable, expression: &str, format: OutputFormat) -> AppResultU {
+    let output = stdout();
+    let output = output.lock();
+    let mut output = BufWriter::new(output);
+
+    let mut results = 0;
+    federation::search(db, attached, &aliases, expression, |origin, meta| {
+        results += 1;
+        format.write_federated(&mut output, &FederatedMeta { origin: origin.to_owned(), meta: meta.clone() })
+    })?;
+
+    db.add_search_history(expression, Some(results))
+}
+
+fn command_server<T: AsRef<Path>>(mut db: Database, db_file: &T, attached: Vec<Attached>, aliases: GlobalAliasTable, config: server::Config, dl_config: download::Config) -> AppResultU {
+    if !db.token_exists()? && config.listens.iter().any(|it| !it.is_local()) {
+        return Err(AppError::Standard("No tokens are registered, so the server only listens on loopback addresses (add one by `noir token add`)"));
+    }
+    let events = EventBus::default();
+    db.set_events(events.clone());
+    let mut dl_db = Database::open(db_file)?;
+    dl_db.set_events(events.clone());
+    let manager = Manager::new(dl_db, dl_config, events.clone());
+    start_server(db, attached, manager, aliases, events, config)?;
+    Ok(())
+}
+
+fn command_source_list(db: &Database) -> AppResultU {
+    for it in db.sources()? {
+        println!("{}\t{}\t{}", it.priority, it.name, it.rows);
+    }
+    Ok(())
+}
+
+fn command_source_priority(db: &Database, source: &str, priority: i64) -> AppResultU {
+    db.set_source_priority(source, priority)
+}
+
+fn command_synonym_add(db: &Database, alias: &str, canonical: &str) -> AppResultU {
+    let alias = Tag::from_str(alias)?;
+    let canonical = Tag::from_str(canonical)?;
+    db.add_synonym(&alias, &canonical)
+}
+
+fn command_synonym_config(db: &Database, case_folding: Option<bool>, unicode: Option<bool>) -> AppResultU {
+    if let Some(case_folding) = case_folding {
+        db.set_setting(CASE_FOLDING, case_folding)?;
+    }
+    if let Some(unicode) = unicode {
+        db.set_setting(UNICODE_NORMALIZATION, unicode)?;
+    }
+    let normalizer = db.tag_normalizer()?;
+    println!("{}\t{}", CASE_FOLDING, on_off(normalizer.case_folding));
+    println!("{}\t{}", UNICODE_NORMALIZATION, on_off(normalizer.unicode));
+    Ok(())
+}
+
+fn command_synonym_list(db: &Database) -> AppResultU {
+    for it in db.synonyms()? {
+        println!("{}\t{}", it.alias, it.canonical);
+    }
+    Ok(())
+}
+
+fn command_synonym_remove(db: &Database, alias: &str) -> AppResultU {
+    if !db.delete_synonym(alias)? {
+        eprintln!("Synonym not found: {}", alias);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn command_synonym_renormalize(db: &Database) -> AppResultU {
+    let _tx = db.transaction()?;
+    let renamed = db.renormalize_tags()?;
+    println!("Renamed {} rows", renamed);
     Ok(())
 }
 
@@ -313,11 +889,53 @@ fn command_tag_add(db: &Database, path: &str, tags: &[&str], source: &str) -> Ap
     Ok(())
 }
 
+fn command_tag_batch(db: &Database, operation: TagOperation, source: &str, tags: &[&str], paths: &[String], dry_run: bool) -> AppResultU {
+    let tags = to_tags(tags)?;
+    let result = db.batch_tags(operation, paths, &tags, source, dry_run)?;
+    if dry_run {
+        println!("Would change {} rows for {} paths", result.rows, result.paths);
+    } else {
+        println!("Changed {} rows for {} paths", result.rows, result.paths);
+    }
+    Ok(())
+}
+
 fn command_tag_clear(db: &Database, path: &str, source: &str) -> AppResultU {
     db.clear_tags(path, source)?;
     Ok(())
 }
 
+fn command_tag_drop(db: &Database, tag: &str, source: Option<&str>) -> AppResultU {
+    let tag = Tag::from_str(tag)?;
+    let deleted = db.drop_tag(&tag, source)?;
+    println!("Deleted {} rows", deleted);
+    Ok(())
+}
+
+fn command_tag_merge(db: &Database, tags: &[&str], into: &str, source: Option<&str>) -> AppResultU {
+    let tags = to_tags(tags)?;
+    let into = Tag::from_str(into)?;
+    let merged = db.merge_tags(&tags, &into, source)?;
+    println!("Merged {} rows", merged);
+    Ok(())
+}
+
+fn command_tag_rename(db: &Database, old: &str, new: &str, source: Option<&str>) -> AppResultU {
+    let old = Tag::from_str(old)?;
+    let new = Tag::from_str(new)?;
+    let _tx = db.transaction()?;
+    let renamed = db.rename_tag(&old, &new, source)?;
+    println!("Renamed {} rows", renamed);
+    Ok(())
+}
+
+fn command_tag_namespaces(db: &Database) -> AppResultU {
+    for it in db.namespaces()? {
+        println!("{}\t{}", it.tags, it.namespace);
+    }
+    Ok(())
+}
+
 fn command_tag_remove(db: &Database, path: &str, tags: &[&str], source: &str) -> AppResultU {
     let tags = to_tags(tags)?;
     db.delete_tags(path, tags.as_slice(), source)?;
@@ -344,6 +962,73 @@ fn command_tag_show(db: &Database, path: Option<&str>) -> AppResultU {
     Ok(())
 }
 
+fn command_token_add(db: &Database, name: &str, scopes: &[&str]) -> AppResultU {
+    let scopes: Vec<Scope> = scopes.iter().map(|it| Scope::from_str(it)).collect::<AppResult<_>>()?;
+    let secret = generate_secret()?;
+    db.add_token(name, &secret, &scopes)?;
+    println!("{}", secret);
+    Ok(())
+}
+
+fn command_token_list(db: &Database) -> AppResultU {
+    for token in db.tokens()? {
+        println!("{}\t{}\t{}", token.name, join_scopes(&token.scopes), token.created);
+    }
+    Ok(())
+}
+
+fn command_token_revoke(db: &Database, name: &str) -> AppResultU {
+    if !db.delete_token(name)? {
+        eprintln!("Token not found: {}", name);
+        exit(1);
+    }
+    Ok(())
+}
+
+fn command_tag_stats(db: &Database, source: Option<&str>, with: Option<&str>, limit: Option<usize>) -> AppResultU {
+    if let Some(with) = with {
+        for it in db.tag_cooccurrences(with, limit.unwrap_or(usize::MAX >> 1))? {
+            println!("{}\t{}", it.count, it.tag);
+        }
+        return Ok(());
+    }
+
+    let counts = db.tag_counts(source)?;
+    for it in counts.iter().take(limit.unwrap_or(counts.len())) {
+        let sources: Vec<String> = it.sources.iter().map(|(source, count)| format!("{}={}", source, count)).collect();
+        println!("{}\t{}\t{}", it.count, it.tag, sources.join(" "));
+    }
+    Ok(())
+}
+
+fn command_rating_set(db: &Database, path: &str, rating: Option<u8>) -> AppResultU {
+    let path = Path::new(path).canonicalize()?;
+    db.set_rating(from_path(&path)?, rating)
+}
+
+fn command_rating_show(db: &Database, path: &str) -> AppResultU {
+    let path = Path::new(path).canonicalize()?;
+    let score = db.score(from_path(&path)?)?;
+    let rating = score.rating.map(|it| it.to_string()).unwrap_or_else(|| "-".to_owned());
+    let viewed = score.viewed.map(|it| it.to_string()).unwrap_or_else(|| "-".to_owned());
+    println!("rating\t{}", rating);
+    println!("views\t{}", score.views);
+    println!("viewed\t{}", viewed);
+    Ok(())
+}
+
+fn command_rating_view(db: &Database, path: &str) -> AppResultU {
+    let path = Path::new(path).canonicalize()?;
+    println!("{}", db.add_view(from_path(&path)?)?);
+    Ok(())
+}
+
+fn command_relocate(db: &Database, rewrite: &PrefixRewrite, sample: usize) -> AppResultU {
+    let relocated = db.relocate(rewrite, sample)?;
+    println!("{} images relocated", relocated);
+    Ok(())
+}
+
 fn command_reset(db: &Database) -> AppResultU {
     let stdin = stdin();
     let mut input = "".to_owned();
@@ -388,6 +1073,79 @@ fn command_vacuum(db: &Database, prefix: Option<&str>) -> AppResultU {
     Ok(())
 }
 
+/// `name` (in the data directory) or `name=path` for each `--attach`.
+/// They are opened read-only, and must exist and be migrated already.
+fn attached_databases(matches: &ArgMatches) -> AppResult<Vec<Attached>> {
+    let specs: Vec<&str> = matches.values_of("attach").map(Iterator::collect).unwrap_or_default();
+    let mut result: Vec<Attached> = vec![];
+    for spec in specs {
+        let (name, file) = match spec.split_once('=') {
+            Some((name, path)) => (name, Path::new(path).to_owned()),
+            None => (spec, database_file(spec)?),
+        };
+        if name.is_empty() || name == federation::MAIN || result.iter().any(|it| it.name == name) {
+            return Err(AppError::InvalidAttachment(spec.to_owned()));
+        }
+        if !file.is_file() {
+            return Err(AppError::AttachedDatabaseNotFound(spec.to_owned()));
+        }
+        if !Database::pending_migrations(&file)?.is_empty() {
+            return Err(AppError::AttachedDatabaseOutdated(spec.to_owned()));
+        }
+        result.push(Attached { name: name.to_owned(), db: Database::open_read_only(&file)? });
+    }
+    Ok(result)
+}
+
+fn canonical_paths(paths: &[&str]) -> AppResult<Vec<String>> {
+    let mut result = vec![];
+    for path in paths {
+        let path = Path::new(path).canonicalize()?;
+        result.push(from_path(&path)?.to_owned());
+    }
+    Ok(result)
+}
+
+fn database_file(name: &str) -> AppResult<PathBuf> {
+    let mut path = get_app_dir(AppDataType::UserData, &APP_INFO, "db")?;
+    path.push(format!("{}.sqlite", name));
+    Ok(path)
+}
+
+fn extract_server_config(matches: &ArgMatches) -> AppResult<server::Config> {
+    let port: u16 = matches.value_of("port").unwrap_or("9696").parse()?;
+    let binds: Vec<&str> = matches.values_of("bind").map(Iterator::collect).unwrap_or_else(|| vec!["127.0.0.1"]);
+    let listens = binds.iter().map(|it| Listen::parse(it, port)).collect::<AppResult<_>>()?;
+    let cors_origins = matches.values_of("cors-origin").map(|it| it.map(ToOwned::to_owned).collect()).unwrap_or_default();
+    let download_to = matches.value_of("download-to").map(ToOwned::to_owned);
+    let root = matches.value_of("root").unwrap_or("static").to_owned();
+    Ok(server::Config { cors_origins, download_to, listens, root })
+}
+
+fn extract_download_config(matches: &ArgMatches) -> AppResult<download::Config> {
+    let mut config = download::Config::default();
+    if let Some(workers) = matches.value_of("download-workers") {
+        config.workers = workers.parse()?;
+    }
+    if let Some(per_host) = matches.value_of("download-per-host") {
+        config.per_host = per_host.parse()?;
+        if config.per_host == 0 {
+            return Err(AppError::Standard("`download-per-host` must be at least 1"));
+        }
+    }
+    if let Some(interval) = matches.value_of("download-interval") {
+        config.interval = Duration::from_secs(interval.parse()?);
+    }
+    if let Some(conversions) = matches.values_of("download-convert") {
+        config.hooks.convert = conversions.map(Conversion::from_str).collect::<AppResult<_>>()?;
+    }
+    config.hooks.command = matches.value_of("download-command").map(ToOwned::to_owned);
+    config.hooks.dated_directory = matches.value_of("download-dated").map(ToOwned::to_owned);
+    config.hooks.tag_script = matches.value_of("download-tag-script").map(ToOwned::to_owned);
+    config.hooks.tag_source = matches.value_of("download-tag-source").map(ToOwned::to_owned);
+    Ok(config)
+}
+
 fn extract_loader_config<'a>(matches: &'a ArgMatches) -> Config<'a> {
     let check_extension = matches.is_present("check-extension");
     let compute_dhash = matches.is_present("dhash");
@@ -410,6 +1168,18 @@ fn join(strings: &[&str]) -> String {
     joined
 }
 
+fn target_paths(db: &Database, aliases: &GlobalAliasTable, paths: &[&str], expression: Option<&str>) -> AppResult<Vec<String>> {
+    if let Some(expression) = expression {
+        let expander = Expander::generate(db, aliases)?;
+        return db.select_paths(expander.expand_str(expression)?.as_ref());
+    }
+    canonical_paths(paths)
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
 fn to_tags(tags: &[&str]) -> AppResult<Vec<Tag>> {
     tags.iter().map(|it| Tag::from_str(it)).collect()
 }
@@ -0,0 +1,326 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, Lines, Write};
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::offset::Utc;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::database::Database;
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::image_format::format_name;
+use crate::meta::{Dimensions, FileMeta, Meta};
+use crate::rating::Score;
+use crate::search_history::{HistoryFilter, SearchHistory};
+use crate::tag::Tag;
+
+
+
+/// Version of the export format. `import` rejects the newer ones.
+pub const FORMAT_VERSION: u32 = 1;
+
+
+/// A line of the exported JSON-lines. The first one is `Header`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Header { version: u32, created: DateTime<Utc> },
+    Image(ImageRecord),
+    Alias { name: String, expression: String, recursive: bool },
+    History(SearchHistory),
+    Collection { name: String, created: Option<DateTime<Utc>>, paths: Vec<String> },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImageRecord {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    pub animation: bool,
+    pub size: u32,
+    pub dhash: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    pub accessed: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub score: Score,
+    #[serde(default)]
+    pub tags: Vec<SourcedTag>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SourcedTag {
+    pub tag: String,
+    pub source: String,
+}
+
+/// What `import` does for the entries which exist in the database
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    /// Keep the existing entries
+    Skip,
+    /// Replace the existing entries (tags are replaced per source)
+    Overwrite,
+    /// Keep the existing entries, but add the imported tags and collection items
+    Union,
+}
+
+/// Replaces the path prefix `from` with `to` (e.g. `/home/alice/pics:/mnt/pics`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefixRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ImportResult {
+    pub images: usize,
+    pub aliases: usize,
+    pub history: usize,
+    pub collections: usize,
+    pub skipped: usize,
+}
+
+
+impl ImageRecord {
+    pub fn new(meta: &Meta, score: Score, tags: Vec<SourcedTag>) -> Self {
+        ImageRecord {
+            path: meta.file.path.clone(),
+            width: meta.dimensions.width,
+            height: meta.dimensions.height,
+            format: meta.format.to_owned(),
+            animation: meta.animation,
+            size: meta.file.size,
+            dhash: meta.dhash.clone(),
+            created: meta.file.created,
+            modified: meta.file.modified,
+            accessed: meta.file.accessed,
+            score,
+            tags,
+        }
+    }
+
+    pub fn to_meta(&self) -> AppResult<Meta> {
+        let format = format_name(&self.format).ok_or_else(|| AppError::InvalidArchive(format!("Unknown image format: {}", self.format)))?;
+        Ok(Meta {
+            animation: self.animation,
+            dhash: self.dhash.clone(),
+            dimensions: Dimensions { width: self.width, height: self.height },
+            file: FileMeta {
+                path: self.path.clone(),
+                size: self.size,
+                created: self.created,
+                modified: self.modified,
+                accessed: self.accessed,
+            },
+            format,
+        })
+    }
+
+    /// Tags grouped by the source
+    fn tags_by_source(&self) -> AppResult<BTreeMap<&str, Vec<Tag>>> {
+        let mut result: BTreeMap<&str, Vec<Tag>> = BTreeMap::new();
+        for it in &self.tags {
+            result.entry(&it.source).or_default().push(Tag::from_str(&it.tag)?);
+        }
+        Ok(result)
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        use ConflictPolicy::*;
+
+        let result = match s {
+            "skip" => Skip,
+            "overwrite" => Overwrite,
+            "union" => Union,
+            _ => return Err(AppError::InvalidConflictPolicy(s.to_owned())),
+        };
+        Ok(result)
+    }
+}
+
+impl FromStr for PrefixRewrite {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        let (from, to) = s.split_once(':').ok_or_else(|| AppError::InvalidPrefixRewrite(s.to_owned()))?;
+        if from.is_empty() {
+            return Err(AppError::InvalidPrefixRewrite(s.to_owned()));
+        }
+        Ok(PrefixRewrite { from: from.to_owned(), to: to.to_owned() })
+    }
+}
+
+impl PrefixRewrite {
+    /// Rewrites the path if it is `from` or under `from` (`/pics` does not match `/pictures`)
+    pub fn apply(&self, path: &str) -> String {
+        let from = self.from.trim_end_matches('/');
+        let to = self.to.trim_end_matches('/');
+        match path.strip_prefix(from) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", to, rest),
+            _ => path.to_owned(),
+        }
+    }
+}
+
+
+/// Writes the all images (with tags and scores), aliases, search history and collections as JSON-lines
+pub fn export<W: Write>(db: &Database, out: &mut W) -> AppResultU {
+    write_record(out, &Record::Header { version: FORMAT_VERSION, created: Utc::now() })?;
+
+    let mut metas = vec![];
+    db.select("1", false, |meta, _vacuumed| {
+        metas.push(meta.clone());
+        Ok(())
+    })?;
+    for meta in &metas {
+        let path = &meta.file.path;
+        let tags = db.sourced_tags_by_path(path)?.into_iter().map(|(tag, source)| SourcedTag { tag, source }).collect();
+        let record = ImageRecord::new(meta, db.score(path)?, tags);
+        write_record(out, &Record::Image(record))?;
+    }
+
+    let mut aliases: Vec<_> = db.aliases()?.into_iter().collect();
+    aliases.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, alias) in aliases {
+        write_record(out, &Record::Alias { name, expression: alias.expression, recursive: alias.recursive })?;
+    }
+
+    for entry in db.search_history(&HistoryFilter::default())? {
+        write_record(out, &Record::History(entry))?;
+    }
+
+    for collection in db.collections()? {
+        let paths = db.collection_paths(&collection.name)?;
+        write_record(out, &Record::Collection { name: collection.name, created: collection.created, paths })?;
+    }
+
+    Ok(())
+}
+
+/// Merges the exported JSON-lines into the database
+pub fn import<R: BufRead>(db: &Database, input: R, policy: ConflictPolicy, rewrite: Option<&PrefixRewrite>) -> AppResult<ImportResult> {
+    let rewrite = |path: &str| rewrite.map(|it| it.apply(path)).unwrap_or_else(|| path.to_owned());
+    let mut lines = input.lines();
+
+    let header = lines.next().transpose()?.ok_or_else(|| AppError::InvalidArchive("Empty".to_owned()))?;
+    match serde_json::from_str::<Record>(&header)? {
+        Record::Header { version, .. } if version <= FORMAT_VERSION => (),
+        Record::Header { version, .. } => return Err(AppError::InvalidArchive(format!("Unsupported version: {}", version))),
+        _ => return Err(AppError::InvalidArchive("No header".to_owned())),
+    }
+
+    db.with_savepoint("import", || import_records(db, lines, policy, &rewrite))
+}
+
+
+fn import_records<R: BufRead>(db: &Database, lines: Lines<R>, policy: ConflictPolicy, rewrite: &dyn Fn(&str) -> String) -> AppResult<ImportResult> {
+    let mut result = ImportResult::default();
+    let mut aliases: HashSet<String> = db.aliases()?.into_keys().collect();
+    let mut collections: HashSet<String> = db.collections()?.into_iter().map(|it| it.name).collect();
+
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Record>(&line)? {
+            Record::Header { .. } => return Err(AppError::InvalidArchive("Duplicated header".to_owned())),
+            Record::Image(mut record) => {
+                record.path = rewrite(&record.path);
+                if import_image(db, &record, policy)? {
+                    result.images += 1;
+                } else {
+                    result.skipped += 1;
+                }
+            },
+            Record::Alias { name, expression, recursive } => {
+                if policy != ConflictPolicy::Overwrite && aliases.contains(&name) {
+                    result.skipped += 1;
+                    continue;
+                }
+                db.upsert_alias(&name, &expression, recursive)?;
+                aliases.insert(name);
+                result.aliases += 1;
+            },
+            Record::History(entry) => {
+                if db.import_search_history(&entry, policy == ConflictPolicy::Overwrite)? {
+                    result.history += 1;
+                } else {
+                    result.skipped += 1;
+                }
+            },
+            Record::Collection { name, paths, .. } => {
+                let exists = collections.contains(&name);
+                if exists && policy == ConflictPolicy::Skip {
+                    result.skipped += 1;
+                    continue;
+                }
+                if !exists {
+                    db.create_collection(&name)?;
+                    collections.insert(name.clone());
+                }
+                let mut items = if exists && policy == ConflictPolicy::Union { db.collection_paths(&name)? } else { vec![] };
+                for path in paths.iter().map(|it| rewrite(it)) {
+                    if !items.contains(&path) && db.path_exists(&path)? {
+                        items.push(path);
+                    }
+                }
+                db.set_collection_paths(&name, &items)?;
+                result.collections += 1;
+            },
+        }
+    }
+
+    Ok(result)
+}
+
+
+/// Returns false if skipped
+fn import_image(db: &Database, record: &ImageRecord, policy: ConflictPolicy) -> AppResult<bool> {
+    use ConflictPolicy::*;
+
+    let exists = db.path_exists(&record.path)?;
+    let tags = record.tags_by_source()?;
+
+    match policy {
+        Skip if exists => return Ok(false),
+        Overwrite | Skip => {
+            db.upsert(&record.to_meta()?)?;
+            db.set_score(&record.path, &record.score)?;
+            for (source, tags) in &tags {
+                db.set_tags(&record.path, tags, source)?;
+            }
+        },
+        Union => {
+            let score = if exists {
+                let current = db.score(&record.path)?;
+                Score {
+                    rating: current.rating.or(record.score.rating),
+                    views: current.views.max(record.score.views),
+                    viewed: current.viewed.max(record.score.viewed),
+                }
+            } else {
+                db.upsert(&record.to_meta()?)?;
+                record.score.clone()
+            };
+            db.set_score(&record.path, &score)?;
+            for (source, tags) in &tags {
+                db.add_tags(&record.path, tags, source)?;
+            }
+        },
+    }
+
+    Ok(true)
+}
+
+fn write_record<W: Write>(out: &mut W, record: &Record) -> AppResultU {
+    serde_json::to_writer(&mut *out, record)?;
+    writeln!(out)?;
+    Ok(())
+}
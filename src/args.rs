@@ -10,12 +10,24 @@ pub fn build_cli() -> App<'static, 'static> {
         .long("format")
         .takes_value(true);
 
+    let source_option = Arg::with_name("source")
+        .help("Only for this tag source")
+        .short("s")
+        .long("source")
+        .takes_value(true);
+
     app_from_crate!()
         .arg(Arg::with_name("database-name")
              .help("Database name")
              .short("n")
              .long("name")
              .takes_value(true))
+        .arg(Arg::with_name("attach")
+             .help("Also search another database (`name` or `name=path`)")
+             .long("attach")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1))
         .arg(Arg::with_name("database-path")
              .help("Path to *.sqlite")
              .short("p")
@@ -46,6 +58,56 @@ pub fn build_cli() -> App<'static, 'static> {
                     .arg(Arg::with_name("name"))
                     .arg(Arg::with_name("expression")
                          .min_values(0)))
+        .subcommand(SubCommand::with_name("collection")
+                    .alias("c")
+                    .about("Manage ordered collections of images")
+                    .subcommand(SubCommand::with_name("add")
+                                .alias("a")
+                                .about("Add images to the collection")
+                                .arg(Arg::with_name("at")
+                                     .help("Position to insert (Default: the end)")
+                                     .long("at")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("path")
+                                     .required(true)
+                                     .min_values(1)))
+                    .subcommand(SubCommand::with_name("create")
+                                .about("Create collection")
+                                .arg(Arg::with_name("name")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("delete")
+                                .about("Delete collection")
+                                .arg(Arg::with_name("name")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("export")
+                                .alias("e")
+                                .about("Output the images of the collection in order")
+                                .arg(format.clone())
+                                .arg(Arg::with_name("name")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List collections, or the paths of the collection")
+                                .arg(Arg::with_name("name")))
+                    .subcommand(SubCommand::with_name("move")
+                                .alias("m")
+                                .about("Move the image in the collection")
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("path")
+                                     .required(true))
+                                .arg(Arg::with_name("position")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("remove")
+                                .alias("r")
+                                .about("Remove images from the collection")
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("path")
+                                     .required(true)
+                                     .min_values(1))))
         .subcommand(SubCommand::with_name("completions")
                     .about("Generates completion scripts for your shell")
                     .arg(Arg::with_name("shell")
@@ -63,6 +125,14 @@ pub fn build_cli() -> App<'static, 'static> {
                          .help("Chunk size")
                          .long("chunk")
                          .takes_value(true)))
+        .subcommand(SubCommand::with_name("db")
+                    .about("Manage database")
+                    .subcommand(SubCommand::with_name("migrate")
+                                .about("Apply schema migrations (backups are made before destructive ones)")
+                                .arg(Arg::with_name("dry-run")
+                                     .help("Only show the pending migrations")
+                                     .long("dry-run")
+                                     .takes_value(false))))
         .subcommand(SubCommand::with_name("expand")
                     .about("Show alias expanded expression")
                     .arg(Arg::with_name("full")
@@ -72,13 +142,105 @@ pub fn build_cli() -> App<'static, 'static> {
                          .takes_value(false))
                     .arg(Arg::with_name("expression")
                          .required(true)))
+        .subcommand(SubCommand::with_name("export")
+                    .about("Export images, tags, aliases, search history and collections as JSON-lines")
+                    .arg(Arg::with_name("output")
+                         .help("Output file (default: stdout)")
+                         .short("o")
+                         .long("output")
+                         .takes_value(true)))
         .subcommand(SubCommand::with_name("get")
                     .about("Get image information")
                     .arg(format.clone())
                     .arg(Arg::with_name("path")
                          .required(true)))
         .subcommand(SubCommand::with_name("history")
-                    .about("Search expression history"))
+                    .about("Search expression history")
+                    .subcommand(SubCommand::with_name("clear")
+                                .about("Delete the unpinned entries")
+                                .arg(Arg::with_name("older-than")
+                                     .help("Only the entries not used for the duration (e.g. `30 days`)")
+                                     .long("older-than")
+                                     .takes_value(true)))
+                    .subcommand(SubCommand::with_name("delete")
+                                .about("Delete entry")
+                                .arg(Arg::with_name("expression")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List entries with the uses, the results and the last used time")
+                                .arg(Arg::with_name("filter")
+                                     .help("Substring of the expression")
+                                     .short("f")
+                                     .long("filter")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("limit")
+                                     .short("l")
+                                     .long("limit")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("pinned")
+                                     .help("Only the pinned entries")
+                                     .short("p")
+                                     .long("pinned")
+                                     .takes_value(false)))
+                    .subcommand(SubCommand::with_name("max-size")
+                                .about("Show or set the number of the unpinned entries to keep")
+                                .arg(Arg::with_name("size")
+                                     .required(false)))
+                    .subcommand(SubCommand::with_name("pin")
+                                .about("Pin entry to keep it")
+                                .arg(Arg::with_name("expression")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("unpin")
+                                .about("Unpin entry")
+                                .arg(Arg::with_name("expression")
+                                     .required(true))))
+        .subcommand(SubCommand::with_name("implication")
+                    .about("Manage tag implications (e.g. `cat` implies `animal`)")
+                    .subcommand(SubCommand::with_name("add")
+                                .alias("a")
+                                .about("Add implication")
+                                .arg(Arg::with_name("child")
+                                     .required(true))
+                                .arg(Arg::with_name("parent")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("apply")
+                                .about("Add the implied tags to the all paths")
+                                .arg(Arg::with_name("source")
+                                     .help("Tag source of the added tags")
+                                     .short("s")
+                                     .long("source")
+                                     .takes_value(true)))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List implications"))
+                    .subcommand(SubCommand::with_name("remove")
+                                .alias("r")
+                                .about("Remove implication")
+                                .arg(Arg::with_name("child")
+                                     .required(true))
+                                .arg(Arg::with_name("parent")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("show")
+                                .about("Show the tags implied by the tag")
+                                .arg(Arg::with_name("tag")
+                                     .required(true))))
+        .subcommand(SubCommand::with_name("import")
+                    .about("Merge the exported JSON-lines into the database")
+                    .arg(Arg::with_name("conflict")
+                         .help("For the existing entries")
+                         .short("c")
+                         .long("conflict")
+                         .takes_value(true)
+                         .possible_values(&["skip", "overwrite", "union"]))
+                    .arg(Arg::with_name("rewrite")
+                         .help("Rewrite path prefix (e.g. `/old/pics:/new/pics`)")
+                         .short("r")
+                         .long("rewrite")
+                         .takes_value(true))
+                    .arg(Arg::with_name("input")
+                         .help("Input file (default: stdin)")
+                         .required(false)))
         .subcommand(
             load_args(
                 SubCommand::with_name("load")
@@ -112,8 +274,111 @@ pub fn build_cli() -> App<'static, 'static> {
                     .arg(format.clone()))
         .subcommand(SubCommand::with_name("path")
                     .about("Show database path"))
+        .subcommand(SubCommand::with_name("rating")
+                    .about("Manage ratings and view counts")
+                    .subcommand(SubCommand::with_name("clear")
+                                .about("Clear rating")
+                                .arg(Arg::with_name("path")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("set")
+                                .alias("s")
+                                .about("Set rating (0 to 5)")
+                                .arg(Arg::with_name("path")
+                                     .required(true))
+                                .arg(Arg::with_name("rating")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("show")
+                                .alias("S")
+                                .about("Show rating and view count")
+                                .arg(Arg::with_name("path")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("view")
+                                .about("Increment view count")
+                                .arg(Arg::with_name("path")
+                                     .required(true))))
+        .subcommand(SubCommand::with_name("relocate")
+                    .about("Rewrite the path prefix of the all entries (e.g. after remounting a disk)")
+                    .arg(Arg::with_name("sample")
+                         .help("Number of the relocated files to verify before committing (0 skips)")
+                         .short("s")
+                         .long("sample")
+                         .takes_value(true))
+                    .arg(Arg::with_name("old-prefix")
+                         .required(true))
+                    .arg(Arg::with_name("new-prefix")
+                         .required(true)))
         .subcommand(SubCommand::with_name("reset")
                     .about("Clear all data"))
+        .subcommand(SubCommand::with_name("root")
+                    .about("Manage library roots (named path prefixes to count and move images)")
+                    .subcommand(SubCommand::with_name("add")
+                                .alias("a")
+                                .about("Register root")
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("path")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List roots with the number of images and the total size"))
+                    .subcommand(SubCommand::with_name("move")
+                                .about("Change the path of root (e.g. another mount point) and relocate its images")
+                                .arg(Arg::with_name("sample")
+                                     .help("Number of the relocated files to verify before committing (0 skips)")
+                                     .short("s")
+                                     .long("sample")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("path")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("remove")
+                                .alias("r")
+                                .about("Unregister root (images are kept)")
+                                .arg(Arg::with_name("name")
+                                     .required(true))))
+        .subcommand(SubCommand::with_name("saved")
+                    .about("Manage saved searches (`@name` in expressions)")
+                    .subcommand(SubCommand::with_name("add")
+                                .alias("a")
+                                .about("Add or update saved search")
+                                .arg(Arg::with_name("description")
+                                     .help("Description")
+                                     .short("d")
+                                     .long("description")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("sort")
+                                     .help("Sort order (e.g. `views DESC`)")
+                                     .short("s")
+                                     .long("sort")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("limit")
+                                     .help("Maximum number of results")
+                                     .short("l")
+                                     .long("limit")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("expression")
+                                     .required(true)
+                                     .min_values(1)))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List saved searches"))
+                    .subcommand(SubCommand::with_name("refresh")
+                                .about("Refresh the cached result counts")
+                                .arg(Arg::with_name("name")))
+                    .subcommand(SubCommand::with_name("remove")
+                                .alias("r")
+                                .about("Remove saved search")
+                                .arg(Arg::with_name("name")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("search")
+                                .alias("s")
+                                .about("Search images by the saved search")
+                                .arg(format.clone())
+                                .arg(Arg::with_name("name")
+                                     .required(true))))
         .subcommand(SubCommand::with_name("search")
                     .alias("s")
                     .alias("select")
@@ -125,18 +390,65 @@ pub fn build_cli() -> App<'static, 'static> {
                          .long("vacuum")
                          .takes_value(false))
                     .arg(Arg::with_name("where")
-                         .help("SQL Where clause")
+                         .help("SQL Where clause (e.g. `rating >= 4 ORDER BY views DESC`)")
                          .required(true)
                          .min_values(1)))
         .subcommand(SubCommand::with_name("server")
                     .about("Web App")
+                    .arg(Arg::with_name("bind")
+                         .help("Address to bind (`address`, `address:port` or `unix:/path/to/socket`) [default: 127.0.0.1]. Non-loopback addresses require tokens")
+                         .short("b")
+                         .long("bind")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1))
+                    .arg(Arg::with_name("cors-origin")
+                         .help("Allowed origin for CORS (No cross-origin access if not given)")
+                         .long("cors-origin")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1))
                     .arg(Arg::with_name("download-to")
                          .help("Download to this directory")
                          .short("d")
                          .long("download-to")
                          .takes_value(true))
+                    .arg(Arg::with_name("download-workers")
+                         .help("Number of concurrent downloads")
+                         .long("download-workers")
+                         .takes_value(true))
+                    .arg(Arg::with_name("download-per-host")
+                         .help("Number of concurrent downloads for each host")
+                         .long("download-per-host")
+                         .takes_value(true))
+                    .arg(Arg::with_name("download-interval")
+                         .help("Minimum interval (seconds) between downloads for each host")
+                         .long("download-interval")
+                         .takes_value(true))
+                    .arg(Arg::with_name("download-command")
+                         .help("Command to run with the path and JSON meta of each downloaded file")
+                         .long("download-command")
+                         .takes_value(true))
+                    .arg(Arg::with_name("download-convert")
+                         .help("Convert downloaded files (e.g. `webp:png`)")
+                         .long("download-convert")
+                         .takes_value(true)
+                         .multiple(true)
+                         .number_of_values(1))
+                    .arg(Arg::with_name("download-dated")
+                         .help("Move downloaded files into dated subdirectory (e.g. `%Y/%m`)")
+                         .long("download-dated")
+                         .takes_value(true))
+                    .arg(Arg::with_name("download-tag-script")
+                         .help("Tag generator script for downloaded files")
+                         .long("download-tag-script")
+                         .takes_value(true))
+                    .arg(Arg::with_name("download-tag-source")
+                         .help("Tag source for `download-tag-script`")
+                         .long("download-tag-source")
+                         .takes_value(true))
                     .arg(Arg::with_name("port")
-                         .help("Server port")
+                         .help("Server port (for `bind` without port)")
                          .short("p")
                          .long("port")
                          .takes_value(true))
@@ -145,6 +457,49 @@ pub fn build_cli() -> App<'static, 'static> {
                          .short("r")
                          .long("root")
                          .takes_value(true)))
+        .subcommand(SubCommand::with_name("source")
+                    .about("Manage tag sources")
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List tag sources with their priorities"))
+                    .subcommand(SubCommand::with_name("priority")
+                                .about("Set the priority of the source (Default: 0)")
+                                .arg(Arg::with_name("source")
+                                     .required(true))
+                                .arg(Arg::with_name("priority")
+                                     .required(true)
+                                     .allow_hyphen_values(true))))
+        .subcommand(SubCommand::with_name("synonym")
+                    .about("Manage tag synonyms and normalization")
+                    .subcommand(SubCommand::with_name("add")
+                                .alias("a")
+                                .about("Add synonym")
+                                .arg(Arg::with_name("alias")
+                                     .required(true))
+                                .arg(Arg::with_name("canonical")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("config")
+                                .about("Configure normalization")
+                                .arg(Arg::with_name("case-folding")
+                                     .help("Fold the case of tags")
+                                     .long("case-folding")
+                                     .takes_value(true)
+                                     .possible_values(&["on", "off"]))
+                                .arg(Arg::with_name("unicode")
+                                     .help("Normalize tags by Unicode NFKC")
+                                     .long("unicode")
+                                     .takes_value(true)
+                                     .possible_values(&["on", "off"])))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List synonyms"))
+                    .subcommand(SubCommand::with_name("remove")
+                                .alias("r")
+                                .about("Remove synonym")
+                                .arg(Arg::with_name("alias")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("renormalize")
+                                .about("Rename the existing tags to their canonical forms")))
         .subcommand(SubCommand::with_name("tag")
                     .alias("t")
                     .about("Manage tags")
@@ -158,6 +513,33 @@ pub fn build_cli() -> App<'static, 'static> {
                                 .arg(Arg::with_name("tag")
                                      .required(true)
                                      .min_values(1)))
+                    .subcommand(SubCommand::with_name("batch")
+                                .alias("b")
+                                .about("Add, remove or set tags for multiple paths at once")
+                                .arg(Arg::with_name("operation")
+                                     .required(true)
+                                     .possible_values(&["add", "remove", "set"]))
+                                .arg(Arg::with_name("source")
+                                     .required(true))
+                                .arg(Arg::with_name("tag")
+                                     .min_values(0))
+                                .arg(Arg::with_name("path")
+                                     .help("Target path")
+                                     .short("p")
+                                     .long("path")
+                                     .takes_value(true)
+                                     .multiple(true)
+                                     .number_of_values(1))
+                                .arg(Arg::with_name("where")
+                                     .help("Expression to select target paths")
+                                     .short("w")
+                                     .long("where")
+                                     .takes_value(true)
+                                     .conflicts_with("path"))
+                                .arg(Arg::with_name("dry-run")
+                                     .help("Only show how many rows would change")
+                                     .long("dry-run")
+                                     .takes_value(false)))
                     .subcommand(SubCommand::with_name("clear")
                                 .alias("c")
                                 .about("Clear tags")
@@ -165,6 +547,26 @@ pub fn build_cli() -> App<'static, 'static> {
                                      .required(true))
                                 .arg(Arg::with_name("source")
                                      .required(true)))
+                    .subcommand(SubCommand::with_name("drop")
+                                .alias("d")
+                                .about("Delete the tag from all paths")
+                                .arg(source_option.clone())
+                                .arg(Arg::with_name("tag")
+                                     .required(true)))
+                    .subcommand(SubCommand::with_name("merge")
+                                .alias("m")
+                                .about("Merge tags into a tag for all paths")
+                                .arg(source_option.clone())
+                                .arg(Arg::with_name("into")
+                                     .help("Tag to merge into")
+                                     .long("into")
+                                     .takes_value(true)
+                                     .required(true))
+                                .arg(Arg::with_name("tag")
+                                     .required(true)
+                                     .min_values(1)))
+                    .subcommand(SubCommand::with_name("namespaces")
+                                .about("List tag namespaces"))
                     .subcommand(SubCommand::with_name("remove")
                                 .alias("r")
                                 .about("Remove tags")
@@ -175,6 +577,13 @@ pub fn build_cli() -> App<'static, 'static> {
                                 .arg(Arg::with_name("tag")
                                      .required(true)
                                      .min_values(1)))
+                    .subcommand(SubCommand::with_name("rename")
+                                .about("Rename the tag for all paths")
+                                .arg(source_option.clone())
+                                .arg(Arg::with_name("old")
+                                     .required(true))
+                                .arg(Arg::with_name("new")
+                                     .required(true)))
                     .subcommand(SubCommand::with_name("set")
                                 .alias("s")
                                 .about("Set tags")
@@ -184,11 +593,44 @@ pub fn build_cli() -> App<'static, 'static> {
                                      .required(true))
                                 .arg(Arg::with_name("tag")
                                      .min_values(0)))
+                    .subcommand(SubCommand::with_name("stats")
+                                .about("Show tag counts")
+                                .arg(source_option)
+                                .arg(Arg::with_name("with")
+                                     .help("Show tags co-occurring with this tag")
+                                     .short("w")
+                                     .long("with")
+                                     .takes_value(true))
+                                .arg(Arg::with_name("limit")
+                                     .help("Maximum number of tags")
+                                     .short("l")
+                                     .long("limit")
+                                     .takes_value(true)))
                     .subcommand(SubCommand::with_name("show")
                                 .alias("S")
                                 .about("Show tags")
                                 .arg(Arg::with_name("path")
                                      .required(false))))
+        .subcommand(SubCommand::with_name("token")
+                    .about("Manage API tokens")
+                    .subcommand(SubCommand::with_name("add")
+                                .alias("a")
+                                .about("Add token")
+                                .arg(Arg::with_name("name")
+                                     .required(true))
+                                .arg(Arg::with_name("scope")
+                                     .help("Scopes")
+                                     .required(true)
+                                     .min_values(1)
+                                     .possible_values(&["admin", "alias-admin", "download", "read", "tag"])))
+                    .subcommand(SubCommand::with_name("list")
+                                .alias("l")
+                                .about("List tokens"))
+                    .subcommand(SubCommand::with_name("revoke")
+                                .alias("r")
+                                .about("Revoke token")
+                                .arg(Arg::with_name("name")
+                                     .required(true))))
         .subcommand(SubCommand::with_name("unalias")
                     .alias("s")
                     .about("Unalias")
@@ -0,0 +1,55 @@
+use chrono::DateTime;
+use chrono::offset::Utc;
+use serde_derive::Serialize;
+
+
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Collection {
+    pub name: String,
+    /// Number of items
+    pub items: u64,
+    pub created: Option<DateTime<Utc>>,
+}
+
+
+/// Inserts the paths at the position (or appends), moving the existing ones
+pub fn insert(items: &mut Vec<String>, paths: &[String], at: Option<usize>) {
+    let mut unique: Vec<String> = vec![];
+    for path in paths {
+        if !unique.contains(path) {
+            unique.push(path.clone());
+        }
+    }
+
+    let mut at = at.unwrap_or(items.len());
+    for path in &unique {
+        if let Some(index) = items.iter().position(|it| it == path) {
+            items.remove(index);
+            if index < at {
+                at -= 1;
+            }
+        }
+    }
+    let at = at.min(items.len());
+    items.splice(at..at, unique);
+}
+
+/// Moves the path to the position, and returns false if the path is not a member
+pub fn move_item(items: &mut Vec<String>, path: &str, to: usize) -> bool {
+    let index = match items.iter().position(|it| it == path) {
+        Some(index) => index,
+        None => return false,
+    };
+    let item = items.remove(index);
+    let to = to.min(items.len());
+    items.insert(to, item);
+    true
+}
+
+/// Removes the paths, and returns the number of the removed items
+pub fn remove(items: &mut Vec<String>, paths: &[String]) -> usize {
+    let before = items.len();
+    items.retain(|it| !paths.contains(it));
+    before - items.len()
+}
@@ -1,27 +1,50 @@
 
-use std::collections::HashMap;
-use std::fs::create_dir_all;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{copy, create_dir_all};
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use chrono::DateTime;
 use chrono::offset::Utc;
 use log::info;
-use rusqlite::types::ToSql;
-use rusqlite::{Connection, Row};
+use rusqlite::types::{FromSql, ToSql};
+use rusqlite::{Connection, OpenFlags, Row};
 
 use crate::alias::Alias;
-use crate::defun::{add_distance_function, add_match_functions, add_recent_function};
-use crate::errors::{AppError, AppResult, AppResultU, from_path};
+use crate::archive::PrefixRewrite;
+use crate::collection::Collection;
+use crate::defun::{add_distance_function, add_favorite_rating_function, add_hash_function, add_match_functions, add_recent_function, add_tag_number_function};
+use crate::event::{Event, EventBus};
+use crate::errors::{AppError, AppResult, AppResultU, from_path, query_error};
+use crate::implication::{Implication, Implications};
 use crate::meta::Meta;
-use crate::search_history::SearchHistory;
-use crate::tag::Tag;
+use crate::migration::{self, Migration};
+use crate::rating::{self, Score};
+use crate::root::Root;
+use crate::saved_search::SavedSearch;
+use crate::search_history::{DEFAULT_MAX_SIZE, HistoryFilter, SearchHistory};
+use crate::synonym::{Normalizer, Synonym};
+use crate::tag::{BatchResult, Source, Tag, TagOperation};
+use crate::tag_stats::{Cooccurrence, NamespaceCount, TagCount};
+use crate::token::{Scope, Token, hash_secret, join_scopes, split_scopes};
 
 
 
 pub const SELECT_PREFIX: &str = "SELECT * FROM images WHERE ";
 
+/// Settings for the tag normalization
+pub const CASE_FOLDING: &str = "case-folding";
+pub const UNICODE_NORMALIZATION: &str = "unicode-normalization";
+/// Setting for the number of the unpinned search history entries to keep
+pub const HISTORY_MAX_SIZE: &str = "history-max-size";
+
 pub struct Database {
     connection: Connection,
+    events: Option<EventBus>,
+    /// `tag_normalizer` and `PRAGMA data_version` when it was built
+    normalizer: RefCell<Option<(i64, Arc<Normalizer>)>>,
 }
 
 pub struct Tx<'a> {
@@ -36,21 +59,69 @@ macro_rules! sql {
 }
 
 impl Database {
-    pub fn add_search_history(&self, where_expression: &str) -> AppResultU {
+    /// Adds the rule `child` implies `parent`, unless it makes a cycle
+    pub fn add_implication(&self, child: &Tag, parent: &Tag) -> AppResultU {
+        let implications: Implications = self.implications()?.iter().collect();
+        if child.as_ref() == parent.as_ref() || implications.would_cycle(child.as_ref(), parent.as_ref()) {
+            return Err(AppError::ImplicationCycle(child.as_ref().to_owned(), parent.as_ref().to_owned()));
+        }
+        let args = &[&child as &dyn ToSql, &parent as &dyn ToSql];
+        self.connection.execute("INSERT OR IGNORE INTO implications (child, parent) VALUES (?1, ?2)", args)?;
+        Ok(())
+    }
+
+    /// Registers the library root. The images under it belong to it (or to the longer nested root).
+    pub fn add_root(&self, name: &str, path: &str) -> AppResultU {
+        let now: DateTime<Utc> = Utc::now();
+        let args = &[&name as &dyn ToSql, &path as &dyn ToSql, &now as &dyn ToSql];
+        self.connection.execute("INSERT INTO roots (name, path, created) VALUES (?1, ?2, ?3)", args)?;
+        Ok(())
+    }
+
+    /// Records the use of the expression and trims the history to the max size
+    pub fn add_search_history(&self, where_expression: &str, results: Option<u64>) -> AppResultU {
         let now: DateTime<Utc> = Utc::now();
         let exp = where_expression.trim();
-        let args = &[&exp as &dyn ToSql, &now as &dyn ToSql];
+        let results = results.map(|it| it as i64);
+        let args = &[&exp as &dyn ToSql, &now as &dyn ToSql, &results as &dyn ToSql];
         self.connection.execute(sql!(update_search_history), args)?;
         self.connection.execute(sql!(insert_search_history), args)?;
+        self.trim_search_history()?;
+        Ok(())
+    }
+
+    /// Adds the synonym, unless it makes a cycle
+    pub fn add_synonym(&self, alias: &Tag, canonical: &Tag) -> AppResultU {
+        if self.tag_normalizer()?.would_cycle(alias.as_ref(), canonical.as_ref()) {
+            return Err(AppError::SynonymCycle(alias.as_ref().to_owned(), canonical.as_ref().to_owned()));
+        }
+        let args = &[&alias as &dyn ToSql, &canonical as &dyn ToSql];
+        self.connection.execute("INSERT OR REPLACE INTO synonyms (alias, canonical) VALUES (?1, ?2)", args)?;
+        self.normalizer.replace(None);
         Ok(())
     }
 
     pub fn add_tags(&self, path: &str, tags: &[Tag], source: &str) -> AppResultU {
         self.check_path_existence(path)?;
-        for tag in tags {
-            let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &source as &dyn ToSql];
-            self.connection.execute(sql!(insert_tag), args)?;
+        let tags = self.normalize_tags(tags)?;
+        let priority = self.source_priority(source)?;
+        let now: DateTime<Utc> = Utc::now();
+        let mut changed = 0;
+        for tag in &tags {
+            changed += self.insert_tag(tag.as_ref(), path, source, priority, &now)?;
         }
+        if 0 < changed {
+            self.emit_tags_changed(path, source);
+        }
+        Ok(())
+    }
+
+    pub fn add_token(&self, name: &str, secret: &str, scopes: &[Scope]) -> AppResultU {
+        let now: DateTime<Utc> = Utc::now();
+        let scopes = join_scopes(scopes);
+        let hash = hash_secret(secret);
+        let args = &[&name as &dyn ToSql, &hash as &dyn ToSql, &scopes as &dyn ToSql, &now as &dyn ToSql];
+        self.connection.execute(sql!(insert_token), args)?;
         Ok(())
     }
 
@@ -66,6 +137,13 @@ impl Database {
         Ok(())
     }
 
+    /// `(url, job JSON, created)` of the unfinished or partially failed downloads
+    pub fn queued_jobs(&self) -> AppResult<Vec<(String, String, Option<DateTime<Utc>>)>> {
+        let mut stmt = self.connection.prepare("SELECT url, job, created FROM queue ORDER BY created")?;
+        let result: rusqlite::Result<Vec<_>> = stmt.query_map([], |row: &Row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?.collect();
+        Ok(result?)
+    }
+
     pub fn aliases(&self) -> AppResult<HashMap<String, Alias>> {
         let mut stmt = self.connection.prepare("SELECT * FROM aliases")?;
         let result: rusqlite::Result<HashMap<String, Alias>> = stmt.query_map(
@@ -82,43 +160,237 @@ impl Database {
         Ok(result?)
     }
 
+    /// Adds the implied (normalized) tags to the all paths as `source` atomically, and returns the number of added rows.
+    /// The tags removed by the sources with higher priority are not added back.
+    pub fn apply_implications(&self, source: &str) -> AppResult<usize> {
+        let implications: Implications = self.implications()?.iter().collect();
+        let mut pairs: Vec<(String, String)> = vec![];
+        for tag in self.tags()? {
+            for parent in implications.ancestors(&tag) {
+                if parent != tag {
+                    pairs.push((tag.clone(), parent));
+                }
+            }
+        }
+
+        let normalizer = self.cached_normalizer()?;
+        let priority = self.source_priority(source)?;
+        let now: DateTime<Utc> = Utc::now();
+        let mut changed: BTreeSet<String> = BTreeSet::new();
+        let added = self.with_savepoint("apply_implications", || {
+            let mut added = 0;
+            for (child, parent) in &pairs {
+                let parent = normalizer.normalize(parent);
+                for path in self.paths_by_tag(child)? {
+                    let inserted = self.insert_tag(&parent, &path, source, priority, &now)?;
+                    if 0 < inserted {
+                        added += inserted;
+                        changed.insert(path);
+                    }
+                }
+            }
+            Ok(added)
+        })?;
+
+        for path in &changed {
+            self.emit_tags_changed(path, source);
+        }
+        Ok(added)
+    }
+
+    /// Applies the operation to all of the paths atomically
+    pub fn batch_tags(&self, operation: TagOperation, paths: &[String], tags: &[Tag], source: &str, dry_run: bool) -> AppResult<BatchResult> {
+        let tags = self.normalize_tags(tags)?;
+        let (changed_paths, rows) = self.with_savepoint("batch_tags", || self.batch_tags_inner(operation, paths, &tags, source, dry_run))?;
+        if !dry_run {
+            for path in &changed_paths {
+                self.emit_tags_changed(path, source);
+            }
+        }
+        Ok(BatchResult { paths: changed_paths.len(), rows })
+    }
+
+    fn batch_tags_inner<'a>(&self, operation: TagOperation, paths: &'a [String], tags: &[Tag], source: &str, dry_run: bool) -> AppResult<(Vec<&'a str>, usize)> {
+        let priority = self.source_priority(source)?;
+        let now: DateTime<Utc> = Utc::now();
+        let mut changed_paths = vec![];
+        let mut rows = 0;
+        for path in paths {
+            self.check_path_existence(path)?;
+            let existing = self.tags_by_path_and_source(path, source)?;
+            let diff = operation.diff(&existing, tags);
+            if diff.delete.is_empty() && diff.insert.is_empty() {
+                continue;
+            }
+            changed_paths.push(path.as_str());
+            rows += diff.delete.len() + diff.insert.len();
+            if dry_run {
+                continue;
+            }
+            for tag in &diff.delete {
+                self.remove_tag(tag, path, source, priority, &now)?;
+            }
+            for tag in &diff.insert {
+                self.insert_tag(tag, path, source, priority, &now)?;
+            }
+        }
+        Ok((changed_paths, rows))
+    }
+
+    /// Increments the view counter, and returns the new count
+    pub fn add_view(&self, path: &str) -> AppResult<u64> {
+        self.check_path_existence(path)?;
+        let now: DateTime<Utc> = Utc::now();
+        let args = &[&path as &dyn ToSql, &now as &dyn ToSql];
+        self.connection.execute("UPDATE images SET views = COALESCE(views, 0) + 1, viewed = ?2 WHERE path = ?1", args)?;
+        Ok(self.score(path)?.views)
+    }
+
     pub fn begin(&self) -> AppResultU {
         info!("BEGIN");
         self.connection.execute("BEGIN;", [])?;
         Ok(())
     }
 
+    /// Deletes the unpinned entries (used before `before` if given)
+    pub fn clear_search_history(&self, before: Option<DateTime<Utc>>) -> AppResult<usize> {
+        let args = &[&before as &dyn ToSql];
+        let deleted = self.connection.execute("DELETE FROM search_history WHERE pinned = 0 AND (?1 IS NULL OR modified < ?1)", args)?;
+        Ok(deleted)
+    }
+
     pub fn clear_tags(&self, path: &str, source: &str) -> AppResultU {
         self.check_path_existence(path)?;
-        self.connection.execute(sql!(clear_tags), &[path, &source])?;
+        if 0 < self.connection.execute(sql!(clear_tags), &[path, &source])? {
+            self.emit_tags_changed(path, source);
+        }
         Ok(())
     }
 
+    /// Member paths of the collection in order
+    pub fn collection_paths(&self, name: &str) -> AppResult<Vec<String>> {
+        self.check_collection_existence(name)?;
+        let mut stmt = self.connection.prepare("SELECT path FROM collection_items WHERE collection = ?1 ORDER BY position")?;
+        let result: rusqlite::Result<Vec<String>> = stmt.query_map(&[name], |row: &Row| row.get(0))?.collect();
+        Ok(result?)
+    }
+
+    pub fn collections(&self) -> AppResult<Vec<Collection>> {
+        let mut stmt = self.connection.prepare(sql!(select_collections))?;
+        let result: rusqlite::Result<Vec<Collection>> = stmt.query_map(
+            [],
+            |row: &Row| Ok(Collection { name: row.get(0)?, items: row.get::<_, i64>(1)? as u64, created: row.get(2)? }))?.collect();
+        Ok(result?)
+    }
+
     pub fn commit(&self) -> AppResultU {
         info!("COMMIT");
         self.connection.execute("COMMIT;", [])?;
         Ok(())
     }
 
+    pub fn create_collection(&self, name: &str) -> AppResultU {
+        let now: DateTime<Utc> = Utc::now();
+        let args = &[&name as &dyn ToSql, &now as &dyn ToSql];
+        self.connection.execute("INSERT INTO collections (name, created) VALUES (?1, ?2)", args)?;
+        self.emit(|| Event::CollectionChanged { name: name.to_owned() });
+        Ok(())
+    }
+
+    pub fn delete_collection(&self, name: &str) -> AppResult<bool> {
+        self.connection.execute("DELETE FROM collection_items WHERE collection = ?1", &[name])?;
+        let deleted = self.connection.execute("DELETE FROM collections WHERE name = ?1", &[name])?;
+        if 0 < deleted {
+            self.emit(|| Event::CollectionChanged { name: name.to_owned() });
+        }
+        Ok(0 < deleted)
+    }
+
     fn delete_path(&self, path: &str) -> AppResultU {
         self.connection.execute("DELETE FROM images WHERE path = ?1", &[path])?;
         self.connection.execute("DELETE FROM tags WHERE path = ?1", &[path])?;
+        self.connection.execute("DELETE FROM tag_removals WHERE path = ?1", &[path])?;
+        self.connection.execute("DELETE FROM collection_items WHERE path = ?1", &[path])?;
+        self.emit(|| Event::ImageRemoved { path: path.to_owned() });
         Ok(())
     }
 
     pub fn delete_alias(&self, name: &str) -> AppResultU {
         self.connection.execute("DELETE FROM aliases WHERE name = ?1", &[name])?;
+        self.emit(|| Event::AliasChanged { name: name.to_owned() });
         Ok(())
     }
 
+    /// Deletes the tag from the all paths, and returns the number of deleted rows
+    pub fn drop_tag(&self, tag: &Tag, source: Option<&str>) -> AppResult<usize> {
+        let changed = self.tag_owners(tag, source)?;
+        let args = &[&tag as &dyn ToSql, &source as &dyn ToSql];
+        let deleted = self.connection.execute("DELETE FROM tags WHERE tag = ?1 AND (?2 IS NULL OR source = ?2)", args)?;
+        for (path, source) in &changed {
+            self.emit_tags_changed(path, source);
+        }
+        Ok(deleted)
+    }
+
+    pub fn delete_implication(&self, child: &str, parent: &str) -> AppResult<bool> {
+        let deleted = self.connection.execute("DELETE FROM implications WHERE child = ?1 AND parent = ?2", &[child, parent])?;
+        Ok(0 < deleted)
+    }
+
+    /// The images are kept, and belong to the outer root if any
+    pub fn delete_root(&self, name: &str) -> AppResult<bool> {
+        let deleted = self.connection.execute("DELETE FROM roots WHERE name = ?1", &[name])?;
+        Ok(0 < deleted)
+    }
+
+    pub fn delete_saved_search(&self, name: &str) -> AppResult<bool> {
+        let deleted = self.connection.execute("DELETE FROM saved_searches WHERE name = ?1", &[name])?;
+        if 0 < deleted {
+            self.emit(|| Event::SavedSearchChanged { name: name.to_owned() });
+        }
+        Ok(0 < deleted)
+    }
+
+    pub fn delete_search_history(&self, expression: &str) -> AppResultU {
+        let expression = expression.trim();
+        if self.connection.execute("DELETE FROM search_history WHERE expression = ?1", &[expression])? == 0 {
+            return Err(AppError::HistoryNotFound(expression.to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn delete_synonym(&self, alias: &str) -> AppResult<bool> {
+        let deleted = self.connection.execute("DELETE FROM synonyms WHERE alias = ?1", &[alias])?;
+        self.normalizer.replace(None);
+        Ok(0 < deleted)
+    }
+
     pub fn delete_tags(&self, path: &str, tags: &[Tag], source: &str) -> AppResultU {
-        for tag in tags {
-            let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &source as &dyn ToSql];
-            self.connection.execute(sql!(delete_tag), args)?;
+        self.check_path_existence(path)?;
+        let tags = self.normalize_tags(tags)?;
+        let priority = self.source_priority(source)?;
+        let now: DateTime<Utc> = Utc::now();
+        let mut changed = 0;
+        for tag in &tags {
+            changed += self.remove_tag(tag.as_ref(), path, source, priority, &now)?;
+        }
+        if 0 < changed {
+            self.emit_tags_changed(path, source);
         }
         Ok(())
     }
 
+    pub fn delete_token(&self, name: &str) -> AppResult<bool> {
+        let deleted = self.connection.execute("DELETE FROM tokens WHERE name = ?1", &[name])?;
+        Ok(0 < deleted)
+    }
+
+    pub fn find_by_dhash(&self, dhash: &str) -> AppResult<Vec<Meta>> {
+        let mut stmt = self.connection.prepare("SELECT * FROM images WHERE dhash = ?1")?;
+        let iter = stmt.query_and_then(&[&dhash as &dyn ToSql], from_row)?;
+        iter.collect()
+    }
+
     pub fn get(&self, path: &str) -> AppResult<Option<Meta>> {
         let path = Path::new(path).canonicalize().unwrap_or_else(|_| Path::new(path).to_path_buf());
         let path = from_path(&path)?;
@@ -127,16 +399,123 @@ impl Database {
         iter.next().transpose()
     }
 
+    /// Inserts the tag unless a source with higher priority has removed it
+    fn insert_tag(&self, tag: &str, path: &str, source: &str, priority: i64, now: &DateTime<Utc>) -> AppResult<usize> {
+        let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &source as &dyn ToSql, &priority as &dyn ToSql, now as &dyn ToSql];
+        let inserted = self.connection.execute(sql!(insert_tag), args)?;
+        if 0 < inserted {
+            let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &priority as &dyn ToSql];
+            self.connection.execute(sql!(delete_tag_removals), args)?;
+        }
+        Ok(inserted)
+    }
+
+    /// Adds the exported entry as it is. Returns false if the entry exists and `overwrite` is false.
+    pub fn import_search_history(&self, entry: &SearchHistory, overwrite: bool) -> AppResult<bool> {
+        let verb = if overwrite { "INSERT OR REPLACE" } else { "INSERT OR IGNORE" };
+        let results = entry.results.map(|it| it as i64);
+        let args = &[&entry.expression as &dyn ToSql, &entry.uses, &entry.last_used, &entry.pinned, &results];
+        let sql = format!("{} INTO search_history (expression, uses, created, modified, pinned, results) VALUES (?1, ?2, ?3, ?3, ?4, ?5)", verb);
+        Ok(0 < self.connection.execute(&sql, args)?)
+    }
+
+    pub fn history_max_size(&self) -> AppResult<u64> {
+        let size: Option<i64> = self.setting(HISTORY_MAX_SIZE)?;
+        Ok(size.map(|it| it as u64).unwrap_or(DEFAULT_MAX_SIZE))
+    }
+
+    pub fn implications(&self) -> AppResult<Vec<Implication>> {
+        let mut stmt = self.connection.prepare("SELECT child, parent FROM implications ORDER BY parent, child")?;
+        let result: rusqlite::Result<Vec<Implication>> = stmt.query_map(
+            [],
+            |row: &Row| Ok(Implication { child: row.get(0)?, parent: row.get(1)? }))?.collect();
+        Ok(result?)
+    }
+
+    /// Merges the tags into `into` atomically, and returns the number of the original rows
+    pub fn merge_tags(&self, from: &[Tag], into: &Tag, source: Option<&str>) -> AppResult<usize> {
+        self.with_savepoint("merge_tags", || from.iter().try_fold(0, |n, tag| self.rename_tag(tag, into, source).map(|it| n + it)))
+    }
+
+    /// `tag_normalizer` built once until the synonyms or settings are changed (by any connection)
+    fn cached_normalizer(&self) -> AppResult<Arc<Normalizer>> {
+        let version: i64 = self.connection.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        if let Some((cached, normalizer)) = &*self.normalizer.borrow() {
+            if *cached == version {
+                return Ok(normalizer.clone());
+            }
+        }
+        let normalizer = Arc::new(self.tag_normalizer()?);
+        self.normalizer.replace(Some((version, normalizer.clone())));
+        Ok(normalizer)
+    }
+
+    fn normalize_tags(&self, tags: &[Tag]) -> AppResult<Vec<Tag>> {
+        let normalizer = self.cached_normalizer()?;
+        tags.iter().map(|it| Tag::from_str(&normalizer.normalize(it.as_ref()))).collect()
+    }
+
+    /// Changes the path of the root (e.g. on another machine), and relocates the images (and the nested roots) under it.
+    /// See `relocate` for `sample`.
+    pub fn move_root(&self, name: &str, path: &str, sample: usize) -> AppResult<usize> {
+        let root = self.roots()?.into_iter().find(|it| it.name == name).ok_or_else(|| AppError::RootNotFound(name.to_owned()))?;
+        self.with_savepoint("move_root", || self.move_root_inner(&root, path, sample))
+    }
+
+    fn move_root_inner(&self, root: &Root, path: &str, sample: usize) -> AppResult<usize> {
+        let rewrite = PrefixRewrite { from: root.path.clone(), to: path.to_owned() };
+        let relocated = self.relocate_inner(&rewrite, sample)?;
+        let args = &[&root.name as &dyn ToSql, &path as &dyn ToSql];
+        self.connection.execute("UPDATE roots SET path = ?2 WHERE name = ?1", args)?;
+        Ok(relocated)
+    }
+
+    /// Applies the pending migrations, but fails if any of them is destructive (see `migrate_and_open`)
     pub fn open<T: AsRef<Path>>(file: &T) -> AppResult<Self> {
-        if let Some(dir) = file.as_ref().parent() {
+        Self::open_with(file.as_ref(), false)
+    }
+
+    /// Applies the all pending migrations including the destructive ones (`noir db migrate`)
+    pub fn migrate_and_open<T: AsRef<Path>>(file: &T) -> AppResult<Self> {
+        Self::open_with(file.as_ref(), true)
+    }
+
+    fn open_with(file: &Path, destructive: bool) -> AppResult<Self> {
+        if let Some(dir) = file.parent() {
             create_dir_all(dir)?;
         }
-        let connection = Connection::open(file.as_ref())?;
-        add_distance_function(&connection)?;
-        add_match_functions(&connection)?;
-        add_recent_function(&connection)?;
+        let connection = Connection::open(file)?;
+        add_functions(&connection)?;
         create_table(&connection)?;
-        Ok(Database { connection })
+        migrate(&connection, file, destructive)?;
+        create_index(&connection)?;
+        Ok(Database { connection, events: None, normalizer: RefCell::new(None) })
+    }
+
+    /// Opens the existing database without creating the tables or applying the migrations (e.g. `--attach`)
+    pub fn open_read_only<T: AsRef<Path>>(file: &T) -> AppResult<Self> {
+        let connection = Connection::open_with_flags(file.as_ref(), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        add_functions(&connection)?;
+        if migration::latest_version() < schema_version(&connection)? {
+            return Err(AppError::Standard("Database schema is newer than this version of noir"));
+        }
+        Ok(Database { connection, events: None, normalizer: RefCell::new(None) })
+    }
+
+    /// Migrations which `open` would apply to the database file
+    pub fn pending_migrations<T: AsRef<Path>>(file: &T) -> AppResult<Vec<&'static Migration>> {
+        if !file.as_ref().is_file() {
+            return Ok(vec![]);
+        }
+        let connection = Connection::open_with_flags(file.as_ref(), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let version = schema_version(&connection)?;
+        let mut result = vec![];
+        for it in migration::pending(version) {
+            if !migration_applied(&connection, it)? {
+                result.push(it);
+            }
+        }
+        Ok(result)
     }
 
     pub fn path_exists(&self, path: &str) -> AppResult<bool> {
@@ -144,31 +523,177 @@ impl Database {
         Ok(stmt.exists(&[&path as &dyn ToSql])?)
     }
 
+    fn paths_by_tag(&self, tag: &str) -> AppResult<Vec<String>> {
+        let mut stmt = self.connection.prepare("SELECT DISTINCT path FROM tags WHERE tag = ?1")?;
+        let result: rusqlite::Result<Vec<String>> = stmt.query_map(&[tag], |row: &Row| row.get(0))?.collect();
+        Ok(result?)
+    }
+
+    /// Rewrites the path prefix in the all tables (including the roots under it) atomically, and returns the number of the relocated images.
+    /// Fails unless `sample` relocated images (chosen randomly) exist. `0` skips the verification.
+    pub fn relocate(&self, rewrite: &PrefixRewrite, sample: usize) -> AppResult<usize> {
+        self.with_savepoint("relocate", || self.relocate_inner(rewrite, sample))
+    }
+
+    fn relocate_inner(&self, rewrite: &PrefixRewrite, sample: usize) -> AppResult<usize> {
+        let from = rewrite.from.trim_end_matches('/');
+        let to = rewrite.to.trim_end_matches('/');
+        let args = &[&from as &dyn ToSql, &to as &dyn ToSql];
+        let mut relocated = 0;
+        for table in &["images", "tags", "tag_removals", "collection_items"] {
+            let sql = format!(
+                "UPDATE {} SET path = ?2 || substr(path, length(?1) + 1) WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'",
+                table);
+            let updated = self.connection.execute(&sql, args)?;
+            if *table == "images" {
+                relocated = updated;
+            }
+        }
+        self.connection.execute(
+            "UPDATE roots SET path = ?2 || substr(path, length(?1) + 1) WHERE rtrim(path, '/') = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/'",
+            args)?;
+
+        let args = &[&to as &dyn ToSql, &(sample as i64) as &dyn ToSql];
+        self.check_sample(
+            "SELECT path FROM images WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '/' ORDER BY RANDOM() LIMIT ?2",
+            args)?;
+        Ok(relocated)
+    }
+
+    /// Renames the tag of the all paths, and returns the number of the original rows.
+    /// The rows which already have the new tag are merged.
+    pub fn rename_tag(&self, from: &Tag, to: &Tag, source: Option<&str>) -> AppResult<usize> {
+        if from.as_ref() == to.as_ref() {
+            return Ok(0);
+        }
+        let changed = self.tag_owners(from, source)?;
+        let args = &[&from as &dyn ToSql, &to as &dyn ToSql, &source as &dyn ToSql];
+        self.connection.execute(sql!(rename_tag), args)?;
+        let args = &[&from as &dyn ToSql, &source as &dyn ToSql];
+        let renamed = self.connection.execute("DELETE FROM tags WHERE tag = ?1 AND (?2 IS NULL OR source = ?2)", args)?;
+        for (path, source) in &changed {
+            self.emit_tags_changed(path, source);
+        }
+        Ok(renamed)
+    }
+
+    /// Deletes the tag of the source and of the lower priority sources, and records the removal if any row is deleted
+    fn remove_tag(&self, tag: &str, path: &str, source: &str, priority: i64, now: &DateTime<Utc>) -> AppResult<usize> {
+        let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &source as &dyn ToSql];
+        let mut deleted = self.connection.execute(sql!(delete_tag), args)?;
+        let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &priority as &dyn ToSql];
+        deleted += self.connection.execute(sql!(delete_overridden_tags), args)?;
+        if 0 < deleted {
+            let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &source as &dyn ToSql, now as &dyn ToSql];
+            self.connection.execute(sql!(insert_tag_removal), args)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Renames the existing tags to their canonical forms atomically, and returns the number of the original rows
+    pub fn renormalize_tags(&self) -> AppResult<usize> {
+        let normalizer = self.tag_normalizer()?;
+        self.with_savepoint("renormalize_tags", || {
+            self.tags()?.iter().try_fold(0, |n, tag| {
+                let canonical = normalizer.normalize(tag);
+                if &canonical == tag {
+                    return Ok(n);
+                }
+                self.rename_tag(&Tag::from_str(tag)?, &Tag::from_str(&canonical)?, None).map(|it| n + it)
+            })
+        })
+    }
+
     pub fn reset(&self) -> AppResultU {
         self.connection.execute("DROP TABLE images", [])?;
         self.connection.execute("DROP TABLE tags", [])?;
+        self.connection.execute("DROP TABLE tag_removals", [])?;
         create_table(&self.connection)?;
+        create_index(&self.connection)?;
         Ok(())
     }
 
-    pub fn search_history(&self) -> AppResult<Vec<SearchHistory>> {
-        let mut stmt = self.connection.prepare("SELECT expression, uses FROM search_history ORDER BY modified DESC")?;
-        let result: rusqlite::Result<Vec<SearchHistory>> = stmt.query_map(
+    pub fn schema_version(&self) -> AppResult<u32> {
+        schema_version(&self.connection)
+    }
+
+    pub fn score(&self, path: &str) -> AppResult<Score> {
+        let mut stmt = self.connection.prepare("SELECT rating, views, viewed FROM images WHERE path = ?1")?;
+        let mut iter = stmt.query_and_then(&[path], |row: &Row| -> AppResult<Score> {
+            let views: Option<i64> = row.get(1)?;
+            Ok(Score {
+                rating: row.get(0)?,
+                views: views.unwrap_or(0) as u64,
+                viewed: row.get(2)?,
+            })
+        })?;
+        iter.next().transpose()?.ok_or_else(|| AppError::PathNotFound(path.to_owned()))
+    }
+
+    pub fn roots(&self) -> AppResult<Vec<Root>> {
+        let mut stmt = self.connection.prepare(sql!(select_roots))?;
+        let result: rusqlite::Result<Vec<Root>> = stmt.query_map(
             [],
-            |row: &Row|
-            Ok(
-                SearchHistory {
-                    expression: row.get(0)?,
-                    uses: row.get(1)?
-                }
-            )
+            |row: &Row| Ok(Root {
+                name: row.get(0)?,
+                path: row.get(1)?,
+                images: row.get::<_, i64>(2)? as u64,
+                size: row.get::<_, i64>(3)? as u64,
+                created: row.get(4)?,
+            }))?.collect();
+        Ok(result?)
+    }
+
+    pub fn saved_searches(&self) -> AppResult<HashMap<String, SavedSearch>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT name, expression, description, sort, max_results, count, counted FROM saved_searches")?;
+        let result: rusqlite::Result<HashMap<String, SavedSearch>> = stmt.query_map(
+            [],
+            |row: &Row| {
+                let limit: Option<i64> = row.get(4)?;
+                let count: Option<i64> = row.get(5)?;
+                Ok((
+                    row.get(0)?,
+                    SavedSearch {
+                        name: row.get(0)?,
+                        expression: row.get(1)?,
+                        description: row.get(2)?,
+                        sort: row.get(3)?,
+                        limit: limit.map(|it| it as u64),
+                        count: count.map(|it| it as u64),
+                        counted: row.get(6)?,
+                    }
+                ))
+            })?.collect();
+        Ok(result?)
+    }
+
+    /// Pinned entries first, then the recently used ones
+    pub fn search_history(&self, filter: &HistoryFilter) -> AppResult<Vec<SearchHistory>> {
+        let limit = filter.limit.map(|it| it as i64);
+        let args = &[&filter.q as &dyn ToSql, &filter.pinned as &dyn ToSql, &limit as &dyn ToSql];
+        let mut stmt = self.connection.prepare(sql!(select_search_history))?;
+        let result: rusqlite::Result<Vec<SearchHistory>> = stmt.query_map(
+            args,
+            |row: &Row| {
+                let results: Option<i64> = row.get(3)?;
+                Ok(
+                    SearchHistory {
+                        expression: row.get(0)?,
+                        uses: row.get(1)?,
+                        pinned: row.get(2)?,
+                        results: results.map(|it| it as u64),
+                        last_used: row.get(4)?,
+                    }
+                )
+            }
         )?.collect();
 
         Ok(result?)
     }
 
     pub fn select<F>(&self, where_expression: &str, vacuum: bool, mut f: F) -> AppResultU where F: FnMut(&Meta, bool) -> AppResultU {
-        let mut stmt = self.connection.prepare(&format!("{}{}", SELECT_PREFIX, where_expression))?;
+        let mut stmt = self.connection.prepare(&format!("{}{}", SELECT_PREFIX, where_expression)).map_err(query_error)?;
         let iter = stmt.query_and_then([], from_row)?;
 
         for it in iter {
@@ -183,15 +708,221 @@ impl Database {
         Ok(())
     }
 
+    /// Images of the collection in order
+    pub fn select_collection<F>(&self, name: &str, mut f: F) -> AppResultU where F: FnMut(&Meta) -> AppResultU {
+        self.check_collection_existence(name)?;
+        let mut stmt = self.connection.prepare(
+            "SELECT images.* FROM collection_items JOIN images ON images.path = collection_items.path WHERE collection = ?1 ORDER BY position")?;
+        let iter = stmt.query_and_then(&[name], from_row)?;
+        for it in iter {
+            f(&it?)?;
+        }
+        Ok(())
+    }
+
+    pub fn select_paths(&self, where_expression: &str) -> AppResult<Vec<String>> {
+        let mut paths = vec![];
+        self.select(where_expression, false, |meta, _vacuumed| {
+            paths.push(meta.file.path.clone());
+            Ok(())
+        })?;
+        Ok(paths)
+    }
+
+    pub fn set_history_max_size(&self, size: u64) -> AppResult<usize> {
+        self.set_setting(HISTORY_MAX_SIZE, size as i64)?;
+        self.trim_search_history()
+    }
+
+    pub fn set_history_pinned(&self, expression: &str, pinned: bool) -> AppResultU {
+        let expression = expression.trim();
+        let args = &[&expression as &dyn ToSql, &pinned as &dyn ToSql];
+        if self.connection.execute("UPDATE search_history SET pinned = ?2 WHERE expression = ?1", args)? == 0 {
+            return Err(AppError::HistoryNotFound(expression.to_owned()));
+        }
+        Ok(())
+    }
+
+    pub fn set_saved_search_count(&self, name: &str, count: u64) -> AppResultU {
+        let now: DateTime<Utc> = Utc::now();
+        let args = &[&name as &dyn ToSql, &(count as i64) as &dyn ToSql, &now as &dyn ToSql];
+        self.connection.execute("UPDATE saved_searches SET count = ?2, counted = ?3 WHERE name = ?1", args)?;
+        Ok(())
+    }
+
+    /// Replaces the members of the collection atomically
+    pub fn set_collection_paths(&self, name: &str, paths: &[String]) -> AppResultU {
+        self.check_collection_existence(name)?;
+        self.with_savepoint("set_collection_paths", || self.set_collection_paths_inner(name, paths))?;
+        self.emit(|| Event::CollectionChanged { name: name.to_owned() });
+        Ok(())
+    }
+
+    fn set_collection_paths_inner(&self, name: &str, paths: &[String]) -> AppResultU {
+        self.connection.execute("DELETE FROM collection_items WHERE collection = ?1", &[name])?;
+        for (position, path) in paths.iter().enumerate() {
+            self.check_path_existence(path)?;
+            let args = &[&name as &dyn ToSql, &path as &dyn ToSql, &(position as i64) as &dyn ToSql];
+            self.connection.execute("INSERT INTO collection_items (collection, path, position) VALUES (?1, ?2, ?3)", args)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_events(&mut self, events: EventBus) {
+        self.events = Some(events);
+    }
+
+    pub fn set_rating(&self, path: &str, rating: Option<u8>) -> AppResultU {
+        self.check_path_existence(path)?;
+        let rating = rating.map(rating::validate).transpose()?;
+        let args = &[&path as &dyn ToSql, &rating as &dyn ToSql];
+        if 0 < self.connection.execute("UPDATE images SET rating = ?2 WHERE path = ?1 AND rating IS NOT ?2", args)? {
+            self.emit(|| Event::ImageUpdated { path: path.to_owned() });
+        }
+        Ok(())
+    }
+
+    pub fn set_score(&self, path: &str, score: &Score) -> AppResultU {
+        self.check_path_existence(path)?;
+        let rating = score.rating.map(rating::validate).transpose()?;
+        let args = &[&path as &dyn ToSql, &rating as &dyn ToSql, &(score.views as i64), &score.viewed];
+        self.connection.execute("UPDATE images SET rating = ?2, views = ?3, viewed = ?4 WHERE path = ?1", args)?;
+        self.emit(|| Event::ImageUpdated { path: path.to_owned() });
+        Ok(())
+    }
+
+    pub fn set_setting<T: ToSql>(&self, name: &str, value: T) -> AppResultU {
+        let args = &[&name as &dyn ToSql, &value as &dyn ToSql];
+        self.connection.execute("INSERT OR REPLACE INTO settings (name, value) VALUES (?1, ?2)", args)?;
+        self.normalizer.replace(None);
+        Ok(())
+    }
+
+    pub fn setting<T: FromSql>(&self, name: &str) -> AppResult<Option<T>> {
+        let mut stmt = self.connection.prepare("SELECT value FROM settings WHERE name = ?1")?;
+        let mut iter = stmt.query_and_then(&[name], |row: &Row| row.get(0))?;
+        Ok(iter.next().transpose()?)
+    }
+
+    /// Replaces the tags of the source. The unchanged tags keep their `added` time.
     pub fn set_tags(&self, path: &str, tags: &[Tag], source: &str) -> AppResultU {
-        self.clear_tags(path, source)?;
-        self.add_tags(path, tags, source)?;
+        self.check_path_existence(path)?;
+        let tags = self.normalize_tags(tags)?;
+        let priority = self.source_priority(source)?;
+        let now: DateTime<Utc> = Utc::now();
+        let existing = self.tags_by_path_and_source(path, source)?;
+        let diff = TagOperation::Set.diff(&existing, &tags);
+        let mut changed = 0;
+        for tag in &diff.delete {
+            let args = &[&tag as &dyn ToSql, &path as &dyn ToSql, &source as &dyn ToSql];
+            changed += self.connection.execute(sql!(delete_tag), args)?;
+        }
+        for tag in &diff.insert {
+            changed += self.insert_tag(tag, path, source, priority, &now)?;
+        }
+        if 0 < changed {
+            self.emit_tags_changed(path, source);
+        }
+        Ok(())
+    }
+
+    pub fn set_source_priority(&self, source: &str, priority: i64) -> AppResultU {
+        let args = &[&source as &dyn ToSql, &priority as &dyn ToSql];
+        self.connection.execute("INSERT OR REPLACE INTO source_priorities (source, priority) VALUES (?1, ?2)", args)?;
         Ok(())
     }
 
-    pub fn tag_exists(&self, path: &str, tag: &str) -> AppResult<bool> {
-        let mut stmt = self.connection.prepare("SELECT 1 FROM tags WHERE path = ? AND tag = ?;")?;
-        Ok(stmt.exists(&[&path as &dyn ToSql, &tag as &dyn ToSql])?)
+    /// Known tag sources (which have tags, removals or a priority) ordered by priority
+    pub fn sources(&self) -> AppResult<Vec<Source>> {
+        let mut stmt = self.connection.prepare(sql!(select_sources))?;
+        let result: rusqlite::Result<Vec<Source>> = stmt.query_map(
+            [],
+            |row: &Row| Ok(Source { name: row.get(0)?, priority: row.get(1)?, rows: row.get::<_, i64>(2)? as u64 }))?.collect();
+        Ok(result?)
+    }
+
+    pub fn source_priority(&self, source: &str) -> AppResult<i64> {
+        let mut stmt = self.connection.prepare("SELECT priority FROM source_priorities WHERE source = ?1")?;
+        let mut iter = stmt.query_and_then(&[source], |row: &Row| row.get(0))?;
+        Ok(iter.next().transpose()?.unwrap_or(0))
+    }
+
+    pub fn synonyms(&self) -> AppResult<Vec<Synonym>> {
+        let mut stmt = self.connection.prepare("SELECT alias, canonical FROM synonyms ORDER BY canonical, alias")?;
+        let result: rusqlite::Result<Vec<Synonym>> = stmt.query_map(
+            [],
+            |row: &Row| Ok(Synonym { alias: row.get(0)?, canonical: row.get(1)? }))?.collect();
+        Ok(result?)
+    }
+
+    /// Paths and sources which have the tag
+    fn tag_owners(&self, tag: &Tag, source: Option<&str>) -> AppResult<Vec<(String, String)>> {
+        if self.events.is_none() {
+            return Ok(vec![]);
+        }
+        let mut stmt = self.connection.prepare("SELECT path, source FROM tags WHERE tag = ?1 AND (?2 IS NULL OR source = ?2)")?;
+        let result: rusqlite::Result<Vec<(String, String)>> = stmt.query_map(
+            &[&tag as &dyn ToSql, &source as &dyn ToSql],
+            |row: &Row| Ok((row.get(0)?, row.get(1)?)))?.collect();
+        Ok(result?)
+    }
+
+    /// Tags co-occurring with the tag, ordered by count
+    pub fn tag_cooccurrences(&self, tag: &str, limit: usize) -> AppResult<Vec<Cooccurrence>> {
+        let mut stmt = self.connection.prepare(sql!(select_tag_cooccurrences))?;
+        let result: rusqlite::Result<Vec<Cooccurrence>> = stmt.query_map(
+            &[&tag as &dyn ToSql, &(limit as i64) as &dyn ToSql],
+            |row: &Row| Ok(Cooccurrence { tag: row.get(0)?, count: row.get::<_, i64>(1)? as u64 }))?.collect();
+        Ok(result?)
+    }
+
+    /// Tag counts ordered by count
+    pub fn tag_counts(&self, source: Option<&str>) -> AppResult<Vec<TagCount>> {
+        let mut counts: Vec<TagCount> = vec![];
+        let mut indices: HashMap<String, usize> = HashMap::new();
+
+        let mut stmt = self.connection.prepare(
+            "SELECT tag, COUNT(DISTINCT path) FROM tags WHERE (?1 IS NULL OR source = ?1) GROUP BY tag ORDER BY 2 DESC, tag")?;
+        let mut rows = stmt.query(&[&source as &dyn ToSql])?;
+        while let Some(row) = rows.next()? {
+            let tag: String = row.get(0)?;
+            let count: i64 = row.get(1)?;
+            indices.insert(tag.clone(), counts.len());
+            counts.push(TagCount { tag, count: count as u64, sources: BTreeMap::new() });
+        }
+
+        let mut stmt = self.connection.prepare(
+            "SELECT tag, source, COUNT(*) FROM tags WHERE (?1 IS NULL OR source = ?1) GROUP BY tag, source")?;
+        let mut rows = stmt.query(&[&source as &dyn ToSql])?;
+        while let Some(row) = rows.next()? {
+            let tag: String = row.get(0)?;
+            let count: i64 = row.get(2)?;
+            if let Some(index) = indices.get(&tag) {
+                counts[*index].sources.insert(row.get(1)?, count as u64);
+            }
+        }
+
+        Ok(counts)
+    }
+
+    pub fn tag_normalizer(&self) -> AppResult<Normalizer> {
+        let case_folding = self.setting(CASE_FOLDING)?.unwrap_or(false);
+        let unicode = self.setting(UNICODE_NORMALIZATION)?.unwrap_or(false);
+        let mut normalizer = Normalizer::new(case_folding, unicode);
+        for it in self.synonyms()? {
+            normalizer.add_synonym(&it.alias, &it.canonical);
+        }
+        Ok(normalizer)
+    }
+
+    /// Namespaces ordered by the number of the tags
+    pub fn namespaces(&self) -> AppResult<Vec<NamespaceCount>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT namespace, COUNT(DISTINCT tag) FROM tags WHERE namespace IS NOT NULL GROUP BY namespace ORDER BY 2 DESC, namespace")?;
+        let result: rusqlite::Result<Vec<NamespaceCount>> = stmt.query_map(
+            [],
+            |row: &Row| Ok(NamespaceCount { namespace: row.get(0)?, tags: row.get::<_, i64>(1)? as u64 }))?.collect();
+        Ok(result?)
     }
 
     pub fn tags(&self) -> AppResult<Vec<String>> {
@@ -200,6 +931,19 @@ impl Database {
         Ok(result?)
     }
 
+    pub fn tags_by_path_and_source(&self, path: &str, source: &str) -> AppResult<Vec<String>> {
+        let mut stmt = self.connection.prepare("SELECT tag FROM tags WHERE path = ?1 AND source = ?2")?;
+        let result: rusqlite::Result<Vec<String>> = stmt.query_map(&[path, source], |row: &Row| row.get(0))?.collect();
+        Ok(result?)
+    }
+
+    /// `(tag, source)` pairs
+    pub fn sourced_tags_by_path(&self, path: &str) -> AppResult<Vec<(String, String)>> {
+        let mut stmt = self.connection.prepare("SELECT tag, source FROM tags WHERE path = ?1 ORDER BY source, tag")?;
+        let result: rusqlite::Result<Vec<(String, String)>> = stmt.query_map(&[path], |row: &Row| Ok((row.get(0)?, row.get(1)?)))?.collect();
+        Ok(result?)
+    }
+
     pub fn tags_by_path(&self, path: &str) -> AppResult<Vec<String>> {
         let mut stmt = self.connection.prepare("SELECT DISTINCT(tag) FROM tags WHERE path = ?1")?;
         let result: rusqlite::Result<Vec<String>> = stmt.query_map(&[path], |row: &Row| row.get(0))?.collect();
@@ -219,12 +963,56 @@ impl Database {
         Ok(r as u64)
     }
 
+    pub fn token_exists(&self) -> AppResult<bool> {
+        let mut stmt = self.connection.prepare("SELECT 1 FROM tokens")?;
+        Ok(stmt.exists([])?)
+    }
+
+    pub fn token_scopes(&self, secret: &str) -> AppResult<Option<Vec<Scope>>> {
+        let mut stmt = self.connection.prepare("SELECT scopes FROM tokens WHERE hash = ?1")?;
+        let mut iter = stmt.query_and_then(&[&hash_secret(secret)], |row: &Row| -> AppResult<Vec<Scope>> {
+            let scopes: String = row.get(0)?;
+            split_scopes(&scopes)
+        })?;
+        iter.next().transpose()
+    }
+
+    pub fn tokens(&self) -> AppResult<Vec<Token>> {
+        let mut stmt = self.connection.prepare("SELECT name, scopes, created FROM tokens ORDER BY name")?;
+        let iter = stmt.query_and_then([], |row: &Row| -> AppResult<Token> {
+            let scopes: String = row.get(1)?;
+            Ok(Token {
+                name: row.get(0)?,
+                scopes: split_scopes(&scopes)?,
+                created: row.get(2)?,
+            })
+        })?;
+        iter.collect()
+    }
+
+    /// Deletes the unpinned entries beyond the max size
+    pub fn trim_search_history(&self) -> AppResult<usize> {
+        let size = self.history_max_size()? as i64;
+        let deleted = self.connection.execute(sql!(trim_search_history), &[&size])?;
+        Ok(deleted)
+    }
+
+    /// Runs `f` in the savepoint, and rolls back to it if `f` fails
+    pub fn with_savepoint<T, F: FnOnce() -> AppResult<T>>(&self, name: &str, f: F) -> AppResult<T> {
+        with_savepoint(&self.connection, name, f)
+    }
+
     pub fn transaction(&self) -> AppResult<Tx> {
         self.begin()?;
         let tx = Tx { database: self };
         Ok(tx)
     }
 
+    pub fn update_queue(&self, url: &str, job: &str) -> AppResultU {
+        self.connection.execute("UPDATE queue SET job = ?2 WHERE url = ?1", &[url, job])?;
+        Ok(())
+    }
+
     pub fn upsert(&self, meta: &Meta) -> AppResultU {
         let (width, height) = &meta.dimensions.ratio();
         let args = &[
@@ -241,8 +1029,27 @@ impl Database {
             &meta.file.modified.as_ref(),
             &meta.file.accessed.as_ref(),
         ];
-        self.connection.execute(sql!(update_image), args)?;
+        let updated = self.connection.execute(sql!(update_image), args)?;
         self.connection.execute(sql!(insert_image), args)?;
+        self.emit(|| {
+            let path = meta.file.path.clone();
+            if 0 < updated { Event::ImageUpdated { path } } else { Event::ImageAdded { path } }
+        });
+        Ok(())
+    }
+
+    pub fn upsert_saved_search(&self, saved: &SavedSearch) -> AppResultU {
+        let limit = saved.limit.map(|it| it as i64);
+        let args = &[
+            &saved.name as &dyn ToSql,
+            &saved.expression,
+            &saved.description,
+            &saved.sort,
+            &limit,
+        ];
+        self.connection.execute(sql!(update_saved_search), args)?;
+        self.connection.execute(sql!(insert_saved_search), args)?;
+        self.emit(|| Event::SavedSearchChanged { name: saved.name.clone() });
         Ok(())
     }
 
@@ -250,6 +1057,7 @@ impl Database {
         let args = &[&name as &dyn ToSql, &original as &dyn ToSql, &recursive as &dyn ToSql];
         self.connection.execute(sql!(update_alias), args)?;
         self.connection.execute(sql!(insert_alias), args)?;
+        self.emit(|| Event::AliasChanged { name: name.to_owned() });
         Ok(())
     }
 
@@ -276,12 +1084,40 @@ impl Database {
         Ok(())
     }
 
+    pub fn check_collection_existence(&self, name: &str) -> AppResultU {
+        let mut stmt = self.connection.prepare("SELECT 1 FROM collections WHERE name = ?1")?;
+        if stmt.exists(&[name])? {
+            return Ok(())
+        }
+        Err(AppError::CollectionNotFound(name.to_owned()))
+    }
+
+    /// Fails if any of the selected paths is not a file
+    fn check_sample(&self, sql: &str, args: &[&dyn ToSql]) -> AppResultU {
+        let mut stmt = self.connection.prepare(sql)?;
+        let paths: rusqlite::Result<Vec<String>> = stmt.query_map(args, |row: &Row| row.get(0))?.collect();
+        if let Some(path) = paths?.into_iter().find(|it| !Path::new(it).is_file()) {
+            return Err(AppError::RelocatedFileNotFound(path));
+        }
+        Ok(())
+    }
+
     pub fn check_path_existence(&self, path: &str) -> AppResultU {
         if self.path_exists(path)? {
             return Ok(())
         }
         Err(AppError::PathNotFound(path.to_owned()))
     }
+
+    fn emit<F>(&self, event: F) where F: FnOnce() -> Event {
+        if let Some(ref events) = self.events {
+            events.publish(event());
+        }
+    }
+
+    fn emit_tags_changed(&self, path: &str, source: &str) {
+        self.emit(|| Event::TagsChanged { path: path.to_owned(), source: source.to_owned() });
+    }
 }
 
 
@@ -292,20 +1128,139 @@ impl<'a> Drop for Tx<'a> {
 }
 
 
+fn add_functions(conn: &Connection) -> AppResultU {
+    add_distance_function(conn)?;
+    add_match_functions(conn)?;
+    add_recent_function(conn)?;
+    add_hash_function(conn)?;
+    add_favorite_rating_function(conn)?;
+    add_tag_number_function(conn)?;
+    Ok(())
+}
+
 fn create_table(conn: &Connection) -> AppResultU {
     fn create(conn: &Connection, sql: &str) -> AppResultU {
         conn.execute(sql, [])?;
         Ok(())
     }
+    create(conn, sql!(create_schema_version_table))?;
     create(conn, sql!(create_images_table))?;
     create(conn, sql!(create_tags_table))?;
-    create(conn, sql!(create_tags_index))?;
     create(conn, sql!(create_aliases_table))?;
     create(conn, sql!(create_search_history_table))?;
     create(conn, sql!(create_queue_table))?;
+    create(conn, sql!(create_tokens_table))?;
+    create(conn, sql!(create_implications_table))?;
+    create(conn, sql!(create_synonyms_table))?;
+    create(conn, sql!(create_settings_table))?;
+    create(conn, sql!(create_source_priorities_table))?;
+    create(conn, sql!(create_tag_removals_table))?;
+    create(conn, sql!(create_collections_table))?;
+    create(conn, sql!(create_collection_items_table))?;
+    create(conn, sql!(create_saved_searches_table))?;
+    create(conn, sql!(create_roots_table))?;
+    Ok(())
+}
+
+/// Indices and triggers which may depend on the columns added by the migrations
+fn create_index(conn: &Connection) -> AppResultU {
+    conn.execute(sql!(create_tags_index), [])?;
+    conn.execute(sql!(create_tags_namespace_index), [])?;
+    conn.execute(sql!(create_tags_namespace_trigger), [])?;
+    Ok(())
+}
+
+fn migrate(conn: &Connection, file: &Path, destructive: bool) -> AppResultU {
+    let version = schema_version(conn)?;
+    if migration::latest_version() < version {
+        return Err(AppError::Standard("Database schema is newer than this version of noir"));
+    }
+    if !destructive {
+        for it in migration::pending(version) {
+            if it.destructive && !migration_applied(conn, it)? {
+                return Err(AppError::DestructiveMigration(it.version, it.description));
+            }
+        }
+    }
+    let mut backed_up = false;
+    for it in migration::pending(version) {
+        if migration_applied(conn, it)? {
+            record_migration(conn, it)?;
+            continue;
+        }
+        if it.destructive && !backed_up {
+            let backup = migration::backup_path(file, version);
+            info!("Backup: {:?}", backup);
+            copy(file, &backup)?;
+            backed_up = true;
+        }
+        info!("Migrate: v{} {}", it.version, it.description);
+        with_savepoint(conn, "migration", || apply_migration(conn, it))?;
+    }
     Ok(())
 }
 
+/// Rolls back the changes by `f` if it fails. The savepoint is released even if the rollback fails.
+fn with_savepoint<T, F: FnOnce() -> AppResult<T>>(conn: &Connection, name: &str, f: F) -> AppResult<T> {
+    conn.execute(&format!("SAVEPOINT {}", name), [])?;
+    let result = f();
+    let rolled_back = if result.is_err() {
+        conn.execute(&format!("ROLLBACK TO {}", name), []).map(|_| ())
+    } else {
+        Ok(())
+    };
+    conn.execute(&format!("RELEASE {}", name), [])?;
+    rolled_back?;
+    result
+}
+
+fn apply_migration(conn: &Connection, migration: &Migration) -> AppResultU {
+    for statement in migration.statements {
+        conn.execute(statement, [])?;
+    }
+    record_migration(conn, migration)
+}
+
+/// The added column exists already
+fn migration_applied(conn: &Connection, migration: &Migration) -> AppResult<bool> {
+    match migration.added_column {
+        Some((table, column)) => column_exists(conn, table, column),
+        None => Ok(false),
+    }
+}
+
+fn record_migration(conn: &Connection, migration: &Migration) -> AppResultU {
+    let now: DateTime<Utc> = Utc::now();
+    let args = &[&migration.version as &dyn ToSql, &migration.description as &dyn ToSql, &now as &dyn ToSql];
+    conn.execute("INSERT OR REPLACE INTO schema_version (version, description, applied) VALUES (?1, ?2, ?3)", args)?;
+    Ok(())
+}
+
+fn schema_version(conn: &Connection) -> AppResult<u32> {
+    if !table_exists(conn, "schema_version")? {
+        return Ok(0);
+    }
+    let version: Option<u32> = conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get(0))?;
+    Ok(version.unwrap_or(0))
+}
+
+fn table_exists(conn: &Connection, table: &str) -> AppResult<bool> {
+    let mut stmt = conn.prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")?;
+    Ok(stmt.exists(&[table])?)
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> AppResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        if name == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
 fn from_row(row: &Row) -> AppResult<Meta> {
     use crate::image_format::{from_raw, ImageFormatExt};
     use crate::meta::*;
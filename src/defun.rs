@@ -8,6 +8,10 @@ use rusqlite::functions::FunctionFlags;
 use rusqlite::{Connection, Error, Result};
 use wildmatch::WildMatch;
 
+use crate::rating::from_favorite;
+use crate::tag::tag_number;
+use crate::token::hash_secret;
+
 
 type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
 
@@ -90,3 +94,45 @@ pub fn add_recent_function(db: &Connection) -> Result<()> {
         },
     )
 }
+
+pub fn add_hash_function(db: &Connection) -> Result<()> {
+    // hash_secret(token)
+    db.create_scalar_function(
+        "hash_secret",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
+            let x: String = ctx.get(0)?;
+            Ok(hash_secret(&x))
+        },
+    )
+}
+
+pub fn add_favorite_rating_function(db: &Connection) -> Result<()> {
+    // favorite_rating(tag)
+    db.create_scalar_function(
+        "favorite_rating",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
+            let x: String = ctx.get(0)?;
+            Ok(from_favorite(&x))
+        },
+    )
+}
+
+pub fn add_tag_number_function(db: &Connection) -> Result<()> {
+    // tag_number(value) is NULL unless the value is a finite number
+    db.create_scalar_function(
+        "tag_number",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            assert_eq!(ctx.len(), 1, "called with unexpected number of arguments");
+            let x: Option<String> = ctx.get(0)?;
+            Ok(x.as_deref().and_then(tag_number))
+        },
+    )
+}
@@ -4,10 +4,14 @@ use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 
 use actix_web::{
-    error, dev::HttpResponseBuilder, http::header, http::StatusCode, HttpResponse,
+    error, dev::HttpResponseBuilder, http::StatusCode, HttpResponse,
 };
+use libsqlite3_sys::ErrorCode;
 use nom::{Err as NomErr};
 use failure::Fail;
+use serde::Serialize;
+
+use crate::expression::parser::spans;
 
 
 
@@ -20,32 +24,76 @@ pub type AppResultU = Result<(), AppError>;
 pub enum AppError {
     #[fail(display = "Application directory error: {}", 0)]
     AppDir(app_dirs::AppDirsError),
+    #[fail(display = "Attached database not found: {}", 0)]
+    AttachedDatabaseNotFound(String),
+    #[fail(display = "Attached database has pending migrations (run `noir --name <name> db migrate` or `noir --path <path> db migrate`): {}", 0)]
+    AttachedDatabaseOutdated(String),
     #[fail(display = "clap: {}", 0)]
     Clap(clap::Error),
+    #[fail(display = "Collection not found: {}", 0)]
+    CollectionNotFound(String),
     #[fail(display = "curl: {}", 0)]
     Curl(curl::Error),
+    #[fail(display = "Run `noir db migrate` to apply the destructive migration: v{} {}", 0, 1)]
+    DestructiveMigration(u32, &'static str),
     #[fail(display = "Failed to load directory: {}", 0)]
     DirectoryWalking(walkdir::Error),
     #[fail(display = "{}", 0)]
     Format(std::fmt::Error),
     #[fail(display = "{}", 0)]
     FromSql(rusqlite::types::FromSqlError),
+    #[fail(display = "Invalid duration: {}", 0)]
+    InvalidDuration(String),
     #[fail(display = "Invalid number format")]
     InvalidNumberFormat(std::num::ParseIntError),
+    #[fail(display = "Hook failed: {}", 0)]
+    HookFailed(String),
+    #[fail(display = "HTTP error: {}", 0)]
+    HttpStatus(u32),
+    #[fail(display = "History entry not found: {}", 0)]
+    HistoryNotFound(String),
+    #[fail(display = "Implication makes a cycle: {} -> {}", 0, 1)]
+    ImplicationCycle(String, String),
     #[fail(display = "{}", 0)]
     ImageLoading(image::ImageError),
     #[fail(display = "{}", 0)]
     ImageMetaLoading(image_meta::ImageError),
+    #[fail(display = "Invalid archive: {}", 0)]
+    InvalidArchive(String),
+    #[fail(display = "Invalid attached database (expected unique `name` or `name=path` except `main`): {}", 0)]
+    InvalidAttachment(String),
+    #[fail(display = "Invalid bind address: {}", 0)]
+    InvalidBindAddress(String),
+    #[fail(display = "Invalid conflict policy (expected skip, overwrite or union): {}", 0)]
+    InvalidConflictPolicy(String),
+    #[fail(display = "Invalid conversion (expected `from:to`): {}", 0)]
+    InvalidConversion(String),
+    #[fail(display = "Invalid query: {}", 0)]
+    InvalidQuery(String, Option<usize>),
+    #[fail(display = "Invalid path prefix rewrite (expected `from:to`): {}", 0)]
+    InvalidPrefixRewrite(String),
     #[fail(display = "Invalid output format name: {}", 0)]
     InvalidOutputFormat(String),
+    #[fail(display = "Invalid rating (expected 0 to 5): {}", 0)]
+    InvalidRating(u8),
+    #[fail(display = "Invalid scope: {}", 0)]
+    InvalidScope(String),
     #[fail(display = "Invalid tag format: {}", 0)]
     InvalidTagFormat(String),
+    #[fail(display = "Invalid tag operation: {}", 0)]
+    InvalidTagOperation(String),
     #[fail(display = "IO error: {}", 0)]
     Io(std::io::Error),
     #[fail(display = "Parsing error: {}", 0)]
     Parsing(String),
     #[fail(display = "Path not found: {}", 0)]
     PathNotFound(String),
+    #[fail(display = "Relocated file not found (nothing was changed): {}", 0)]
+    RelocatedFileNotFound(String),
+    #[fail(display = "Root not found: {}", 0)]
+    RootNotFound(String),
+    #[fail(display = "Saved search not found: {}", 0)]
+    SavedSearchNotFound(String),
     #[fail(display = "JSON Error: {}", 0)]
     SerdeJson(serde_json::Error),
     #[fail(display = "YAML Error: {}", 0)]
@@ -54,8 +102,14 @@ pub enum AppError {
     Standard(&'static str),
     #[fail(display = "Database error: {}", 0)]
     Sqlite(rusqlite::Error),
+    #[fail(display = "Forbidden: `{}` scope is required", 0)]
+    Forbidden(&'static str),
+    #[fail(display = "Synonym makes a cycle: {} -> {}", 0, 1)]
+    SynonymCycle(String, String),
     #[fail(display = "Tag generator failed: {}", 0)]
     TagGeneratorFailed(String),
+    #[fail(display = "Unauthorized")]
+    Unauthorized,
     #[fail(display = "UTF-8 error")]
     UnknownUtf8,
     #[fail(display = "UTF-8 error: {}", 0)]
@@ -123,14 +177,155 @@ impl From<std::io::Error> for AppError {
 }
 
 
+impl AppError {
+    /// Stable error code for API clients
+    pub fn code(&self) -> &'static str {
+        use AppError::*;
+
+        match self {
+            AppDir(_) => "app_dir",
+            AttachedDatabaseNotFound(_) => "attached_database_not_found",
+            AttachedDatabaseOutdated(_) => "attached_database_outdated",
+            Clap(_) => "invalid_arguments",
+            CollectionNotFound(_) => "collection_not_found",
+            Curl(_) => "curl",
+            DestructiveMigration(_, _) => "destructive_migration",
+            DirectoryWalking(_) => "directory_walking",
+            Forbidden(_) => "forbidden",
+            Format(_) => "format",
+            FromSql(_) => "database_value",
+            HistoryNotFound(_) => "history_not_found",
+            HookFailed(_) => "hook_failed",
+            HttpStatus(_) => "http_status",
+            ImageLoading(_) => "image_loading",
+            ImplicationCycle(_, _) => "implication_cycle",
+            ImageMetaLoading(_) => "image_meta_loading",
+            InvalidArchive(_) => "invalid_archive",
+            InvalidAttachment(_) => "invalid_attachment",
+            InvalidBindAddress(_) => "invalid_bind_address",
+            InvalidConflictPolicy(_) => "invalid_conflict_policy",
+            InvalidConversion(_) => "invalid_conversion",
+            InvalidDuration(_) => "invalid_duration",
+            InvalidNumberFormat(_) => "invalid_number_format",
+            InvalidOutputFormat(_) => "invalid_output_format",
+            InvalidPrefixRewrite(_) => "invalid_prefix_rewrite",
+            InvalidQuery(_, _) => "invalid_query",
+            InvalidRating(_) => "invalid_rating",
+            InvalidScope(_) => "invalid_scope",
+            InvalidTagFormat(_) => "invalid_tag_format",
+            InvalidTagOperation(_) => "invalid_tag_operation",
+            Io(e) if e.kind() == std::io::ErrorKind::NotFound => "file_not_found",
+            Io(_) => "io",
+            Parsing(_) => "parsing",
+            PathNotFound(_) => "path_not_found",
+            RelocatedFileNotFound(_) => "relocated_file_not_found",
+            RootNotFound(_) => "root_not_found",
+            SavedSearchNotFound(_) => "saved_search_not_found",
+            SerdeJson(_) => "json",
+            SerdeYaml(_) => "yaml",
+            Standard(_) => "error",
+            Sqlite(e) => match sqlite_error_code(e) {
+                Some(ErrorCode::ConstraintViolation) => "conflict",
+                Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => "database_busy",
+                _ => "database",
+            },
+            SynonymCycle(_, _) => "synonym_cycle",
+            TagGeneratorFailed(_) => "tag_generator_failed",
+            Unauthorized => "unauthorized",
+            UnknownUtf8 | Utf8(_) => "invalid_utf8",
+            Void => "not_found",
+            WithPath(e, _) => e.code(),
+        }
+    }
+
+    /// Locates the query error in the expression which the user wrote
+    pub fn in_expression(self, expression: &str) -> AppError {
+        match self {
+            AppError::InvalidQuery(message, None) => {
+                let position = near_token(&message).and_then(|token| {
+                    let spans = spans(expression).ok()?;
+                    let (offset, text) = spans.into_iter().find(|(_, text)| text.trim() == token)?;
+                    Some(offset + text.len() - text.trim_start().len())
+                });
+                AppError::InvalidQuery(message, position)
+            },
+            it => it,
+        }
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            AppError::InvalidQuery(_, position) => *position,
+            AppError::WithPath(e, _) => e.position(),
+            _ => None,
+        }
+    }
+}
+
+/// Converts a failure of preparing the user query
+pub fn query_error(error: rusqlite::Error) -> AppError {
+    match error {
+        rusqlite::Error::SqliteFailure(ref e, Some(ref message)) if e.code == ErrorCode::Unknown =>
+            AppError::InvalidQuery(message.to_owned(), None),
+        it => AppError::Sqlite(it),
+    }
+}
+
+fn near_token(message: &str) -> Option<&str> {
+    // e.g. `near "FROM": syntax error`
+    let (_, rest) = message.split_once("near \"")?;
+    let (token, _) = rest.split_once('"')?;
+    Some(token)
+}
+
+fn sqlite_error_code(error: &rusqlite::Error) -> Option<ErrorCode> {
+    match error {
+        rusqlite::Error::SqliteFailure(e, _) => Some(e.code),
+        _ => None,
+    }
+}
+
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+}
+
 impl error::ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
+        let message = match self {
+            AppError::Void => "Not found".to_owned(),
+            it => it.to_string(),
+        };
         HttpResponseBuilder::new(self.status_code())
-            .set_header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-            .body(self.to_string())
+            .json(ErrorResponse { code: self.code(), message, position: self.position() })
     }
 
     fn status_code(&self) -> StatusCode {
-        StatusCode::BAD_REQUEST
+        use AppError::*;
+
+        match self {
+            Void | AttachedDatabaseNotFound(_) | CollectionNotFound(_) | HistoryNotFound(_) | PathNotFound(_) | RelocatedFileNotFound(_) | RootNotFound(_) |
+            SavedSearchNotFound(_) => StatusCode::NOT_FOUND,
+            AttachedDatabaseOutdated(_) | ImplicationCycle(_, _) | SynonymCycle(_, _) => StatusCode::CONFLICT,
+            Io(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            InvalidArchive(_) | InvalidAttachment(_) | InvalidBindAddress(_) | InvalidConflictPolicy(_) | InvalidConversion(_) | InvalidDuration(_) |
+            InvalidNumberFormat(_) | InvalidOutputFormat(_) | InvalidPrefixRewrite(_) | InvalidQuery(_, _) |
+            InvalidRating(_) | InvalidScope(_) | InvalidTagFormat(_) | InvalidTagOperation(_) | Parsing(_) | UnknownUtf8 | Utf8(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Unauthorized => StatusCode::UNAUTHORIZED,
+            Forbidden(_) => StatusCode::FORBIDDEN,
+            Clap(_) | Standard(_) => StatusCode::BAD_REQUEST,
+            HttpStatus(_) => StatusCode::BAD_GATEWAY,
+            Sqlite(e) => match sqlite_error_code(e) {
+                Some(ErrorCode::ConstraintViolation) => StatusCode::CONFLICT,
+                Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            WithPath(e, _) => e.status_code(),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 }
@@ -0,0 +1,54 @@
+
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{channel, Receiver, Sender};
+use serde_derive::Serialize;
+
+
+
+/// Number of the events buffered for each subscriber.
+/// The subscriber which does not receive them in time is disconnected.
+pub const SUBSCRIBER_BUFFER: usize = 256;
+
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+    AliasChanged { name: String },
+    CollectionChanged { name: String },
+    DownloadCompleted { url: String, path: String },
+    DownloadFailed { url: String, error: String },
+    DownloadProgress { url: String, downloaded: u64, total: u64 },
+    HookFailed { url: String, path: String, hook: String, message: String },
+    ImageAdded { path: String },
+    ImageRemoved { path: String },
+    ImageUpdated { path: String },
+    SavedSearchChanged { name: String },
+    TagsChanged { path: String, source: String },
+}
+
+/// Publishes events to all subscribers (e.g. `/events` clients)
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<Event>>>>,
+}
+
+
+impl EventBus {
+    /// Drops the closed and the lagging subscribers
+    pub fn publish(&self, event: Event) {
+        let mut subscribers = self.subscribers.lock().expect("lock subscribers");
+        let current = std::mem::take(&mut *subscribers);
+        for mut it in current {
+            if it.try_send(event.clone()).is_ok() {
+                subscribers.push(it);
+            }
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = channel(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().expect("lock subscribers").push(tx);
+        rx
+    }
+}
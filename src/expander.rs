@@ -1,5 +1,5 @@
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use log::debug;
 
@@ -7,12 +7,20 @@ use crate::alias::Alias;
 use crate::database::Database;
 use crate::errors::{AppError, AppResult};
 use crate::global_alias::GlobalAliasTable;
+use crate::implication::Implications;
+use crate::saved_search::SavedSearch;
+use crate::synonym::Normalizer;
+use crate::tag::{TagQuery, split_source};
 use crate::expression::{NoirQuery, RawQuery, Expression, parser, string_literal};
 
 
 
 pub struct Expander {
     aliases: HashMap<String, Alias>,
+    implications: Implications,
+    normalizer: Normalizer,
+    saved: HashMap<String, SavedSearch>,
+    sources: HashSet<String>,
 }
 
 
@@ -38,9 +46,16 @@ impl Expander {
         for e in &query.elements {
             match e {
                 Any(c) => result.push(*c),
+                Collection(ref name) => {
+                    result.push_str(&format!("(path in (SELECT path FROM collection_items WHERE collection = {}))", string_literal(name)));
+                },
                 Delimiter(ref s) => result.push_str(s),
-                NoirTag(ref tag) => {
-                    result.push_str(&format!("(path in (SELECT path FROM tags WHERE tag = {}))", string_literal(&tag)));
+                NoirTag(ref tag) => result.push_str(&self.expand_tag(tag)?),
+                SavedSearch(ref name) => {
+                    let saved = self.saved.get(name).ok_or_else(|| AppError::SavedSearchNotFound(name.to_owned()))?;
+                    let saved_query = parser::parse(&saved.expression)?;
+                    let e = self.expand_n(&saved_query, n + 1)?;
+                    result.push_str(&format!("(path in (SELECT path FROM images WHERE {}))", saved.where_clause(e.as_ref())));
                 },
                 PathSegment(ref s) => {
                     result.push_str(&format!("(path like {})", string_literal(&format!("%{}%", s))));
@@ -66,11 +81,52 @@ impl Expander {
         Ok(RawQuery::new(result))
     }
 
+    fn expand_tag(&self, tag: &str) -> AppResult<String> {
+        let (tag, source) = split_source(tag, &self.sources);
+        let tag = self.normalizer.normalize(tag);
+        let mut condition = match TagQuery::parse(&tag)? {
+            TagQuery::Compare { namespace, operator, value } =>
+                format!(
+                    "namespace = {} AND tag_number(substr(tag, {})) {} {}",
+                    string_literal(namespace),
+                    namespace.chars().count() + 2,
+                    operator,
+                    value),
+            TagQuery::Exact(tag) => {
+                let tags = self.implications.descendants(tag);
+                if tags.len() <= 1 {
+                    format!("tag = {}", string_literal(tag))
+                } else {
+                    let tags: Vec<String> = tags.iter().map(|it| string_literal(it)).collect();
+                    format!("tag IN ({})", tags.join(", "))
+                }
+            },
+            TagQuery::Namespace(namespace) =>
+                format!("namespace = {}", string_literal(namespace)),
+            TagQuery::Pattern { namespace, pattern } =>
+                format!("namespace = {} AND match({}, tag)", string_literal(namespace), string_literal(pattern)),
+        };
+        if let Some(source) = source {
+            let operator = if source.negated { "!=" } else { "=" };
+            condition.push_str(&format!(" AND source {} {}", operator, string_literal(source.source)));
+        }
+        Ok(format!("(path in (SELECT path FROM tags WHERE {}))", condition))
+    }
+
     pub fn generate(database: &Database, global_alias_table: &GlobalAliasTable) -> AppResult<Self> {
         let local = database.aliases()?;
         // FIXME remove clone
         let global = global_alias_table.clone().into_iter().collect();
-        Ok(Self::new(local, global))
+        let implications = database.implications()?.iter().collect();
+        let normalizer = database.tag_normalizer()?;
+        let saved = database.saved_searches()?;
+        let sources = database.sources()?.into_iter().map(|it| it.name).collect();
+        let expander = Self::new(local, global)
+            .with_implications(implications)
+            .with_normalizer(normalizer)
+            .with_saved_searches(saved)
+            .with_sources(sources);
+        Ok(expander)
     }
 
     pub fn new(local: HashMap<String, Alias>, global: HashMap<String, Alias>) -> Self {
@@ -83,9 +139,44 @@ impl Expander {
 
         Self {
             aliases,
+            implications: Implications::default(),
+            normalizer: Normalizer::default(),
+            saved: HashMap::new(),
+            sources: HashSet::new(),
         }
     }
 
+    /// `#parent` matches the paths which have the tags implying it
+    pub fn with_implications(mut self, implications: Implications) -> Self {
+        self.implications = implications;
+        self
+    }
+
+    /// `#tag` is written in the canonical form
+    pub fn with_normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    /// `@name` is expanded to the saved search
+    pub fn with_saved_searches(mut self, saved: HashMap<String, SavedSearch>) -> Self {
+        self.saved = saved;
+        self
+    }
+
+    /// `#tag@source` filters the tags by the source, if it is one of `sources`
+    pub fn with_sources(mut self, sources: HashSet<String>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// `WHERE` clause of the saved search with its sort order and limit
+    pub fn expand_saved_search(&self, name: &str) -> AppResult<String> {
+        let saved = self.saved.get(name).ok_or_else(|| AppError::SavedSearchNotFound(name.to_owned()))?;
+        let expanded = self.expand_str(&saved.expression)?;
+        Ok(saved.where_clause(expanded.as_ref()))
+    }
+
     pub fn get_alias(&self, name: &str) -> Option<&Alias> {
         self.aliases.get(name)
     }
@@ -3,9 +3,13 @@
 #[derive(Clone, Debug, PartialEq)]
 pub enum Expression {
     Any(char),
+    /// `in_collection('name')`
+    Collection(String),
     Delimiter(String),
     NoirTag(String),
     PathSegment(String),
+    /// `@name`
+    SavedSearch(String),
     StringLiteral(String),
     Term(String),
 }
@@ -31,9 +35,11 @@ impl ToString for NoirQuery {
         for e in &self.elements {
             match e {
                 Any(c) => result.push(*c),
+                Collection(ref name) => result.push_str(&format!("in_collection({})", string_literal(name))),
                 Delimiter(ref s) => result.push_str(s),
                 NoirTag(ref tag) => result.push_str(&format!("#{}", tag)),
                 PathSegment(ref s) => result.push_str(&format!("`{}`", s)),
+                SavedSearch(ref name) => result.push_str(&format!("@{}", name)),
                 StringLiteral(ref s) => result.push_str(&string_literal(s)),
                 Term(ref s) => result.push_str(s),
             }
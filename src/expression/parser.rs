@@ -1,13 +1,14 @@
 extern crate nom;
 
-use nom::IResult;
+use nom::{Err as NomErr, IResult};
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{anychar, char as cchar, none_of, one_of};
+use nom::character::complete::{anychar, char as cchar, none_of, one_of, satisfy};
+use nom::error::Error as NomError;
 use nom::multi::{many0, many1};
 use nom::sequence::{delimited, preceded, terminated};
 
-use crate::errors::AppResult;
+use crate::errors::{AppError, AppResult};
 
 use super::{Expression as E, NoirQuery};
 
@@ -20,6 +21,19 @@ fn any(input: &str) -> IResult<&str, E> {
     Ok((rest, E::Any(x)))
 }
 
+fn collection(input: &str) -> IResult<&str, E> {
+    let (rest, _) = tag("in_collection")(input)?;
+    let (rest, _) = many0(one_of(" \t"))(rest)?;
+    let (rest, name) = delimited(
+        terminated(cchar('('), many0(one_of(" \t"))),
+        string_literal,
+        preceded(many0(one_of(" \t")), cchar(')')))(rest)?;
+    match name {
+        E::StringLiteral(name) => Ok((rest, E::Collection(name))),
+        _ => unreachable!("string_literal returns StringLiteral"),
+    }
+}
+
 fn delimiter(input: &str) -> IResult<&str, E> {
     let (rest, x) = many1(one_of(DELIMITERS))(input)?;
     Ok((rest, E::Delimiter(x.iter().collect())))
@@ -36,6 +50,12 @@ fn path_segment(input: &str) -> IResult<&str, E> {
     Ok((rest, E::PathSegment(x.iter().collect())))
 }
 
+fn saved_search(input: &str) -> IResult<&str, E> {
+    let (rest, _) = cchar('@')(input)?;
+    let (rest, name) = many1(satisfy(|c| c.is_alphanumeric() || c == '_' || c == '-'))(rest)?;
+    Ok((rest, E::SavedSearch(name.iter().collect())))
+}
+
 fn string_literal(input: &str) -> IResult<&str, E> {
     let ch = alt((preceded(cchar('\''), cchar('\'')), none_of("'")));
 
@@ -54,16 +74,55 @@ fn term(input: &str) -> IResult<&str, E> {
     Ok((rest, E::Term(x.iter().collect())))
 }
 
+fn element(input: &str) -> IResult<&str, E> {
+    alt((noir_tag, collection, saved_search, string_literal, path_segment, term, delimiter, any))(input)
+}
+
 pub fn parse(input: &str) -> AppResult<NoirQuery> {
-    let p = alt((noir_tag, string_literal, path_segment, term, delimiter, any));
-    let (_rest, elements) = many0(p)(input)?;
+    let (_rest, elements) = many0(element)(input).map_err(|it| parse_error(input, it))?;
     Ok(NoirQuery { elements })
 }
 
+/// `(offset, source text)` of each element, located by the remaining input after it
+pub fn spans(input: &str) -> AppResult<Vec<(usize, &str)>> {
+    let mut result = vec![];
+    let mut rest = input;
+    while !rest.is_empty() {
+        let (next, _) = element(rest).map_err(|it| parse_error(input, it))?;
+        result.push((input.len() - rest.len(), &rest[.. rest.len() - next.len()]));
+        rest = next;
+    }
+    Ok(result)
+}
+
+fn parse_error(input: &str, error: NomErr<NomError<&str>>) -> AppError {
+    match error {
+        NomErr::Error(it) | NomErr::Failure(it) =>
+            AppError::InvalidQuery(format!("Unexpected input: {}", it.input), Some(input.len() - it.input.len())),
+        NomErr::Incomplete(_) =>
+            AppError::InvalidQuery("Incomplete expression".to_owned(), Some(input.len())),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_collection() {
+        use E::{Collection as C};
+
+        assert_eq!(
+            collection(r#"in_collection('deck')"#),
+            Ok(("", C("deck".to_owned())))
+        );
+        assert_eq!(
+            collection(r#"in_collection ( 'it''s' ) AND 1"#),
+            Ok((" AND 1", C("it's".to_owned())))
+        );
+        assert!(collection(r#"in_collection"#).is_err());
+    }
+
     #[test]
     fn test_delimiter() {
         use E::{Delimiter as D};
@@ -111,6 +170,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_saved_search() {
+        use E::{SavedSearch as S};
+
+        assert_eq!(
+            saved_search(r#"@best-of_2020 AND 1"#),
+            Ok((" AND 1", S("best-of_2020".to_owned())))
+        );
+        assert!(saved_search(r#"@"#).is_err());
+    }
+
     #[test]
     fn test_string_literal() {
         use E::{StringLiteral as S};
@@ -216,4 +286,12 @@ mod tests {
                     E::Delimiter(")".to_owned()),
                 ]});
     }
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(
+            spans(r#"#cat and 'a b'"#).unwrap(),
+            vec![(0, "#cat"), (4, " "), (5, "and"), (8, " "), (9, "'a b'")]);
+        assert_eq!(spans("").unwrap(), vec![]);
+    }
 }
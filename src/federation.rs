@@ -0,0 +1,56 @@
+use std::iter::once;
+
+use serde_derive::Serialize;
+
+use crate::database::Database;
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::expander::Expander;
+use crate::global_alias::GlobalAliasTable;
+use crate::meta::Meta;
+
+
+
+/// Origin name of the database selected by `--name` or `--path`
+pub const MAIN: &str = "main";
+
+
+/// Another noir database searched together with the main one (`--attach`)
+pub struct Attached {
+    pub name: String,
+    pub db: Database,
+}
+
+/// Image found by the federated search. `origin` selects the database to get its file and tags.
+#[derive(Clone, Debug, Serialize)]
+pub struct FederatedMeta {
+    pub origin: String,
+    #[serde(flatten)]
+    pub meta: Meta,
+}
+
+
+/// Searches the main and the attached databases in order.
+/// The expression is expanded by the aliases, implications and so on of each database,
+/// since each database is queried by its own connection (the tables in the expression are unqualified).
+pub fn search<F>(main: &Database, attached: &[Attached], aliases: &GlobalAliasTable, expression: &str, mut f: F) -> AppResultU
+where F: FnMut(&str, &Meta) -> AppResultU {
+    let databases = once((MAIN, main)).chain(attached.iter().map(|it| (it.name.as_str(), &it.db)));
+    for (origin, db) in databases {
+        let expander = Expander::generate(db, aliases)?;
+        let expanded = expander.expand_str(expression)?;
+        db.select(expanded.as_ref(), false, |meta, _vacuumed| f(origin, meta))
+            .map_err(|it| it.in_expression(expression))?;
+    }
+    Ok(())
+}
+
+/// Database of the `origin` of `FederatedMeta` (the main one if `None`)
+pub fn database<'a>(main: &'a Database, attached: &'a [Attached], origin: Option<&str>) -> AppResult<&'a Database> {
+    match origin {
+        None | Some(MAIN) => Ok(main),
+        Some(origin) => attached.iter()
+            .find(|it| it.name == origin)
+            .map(|it| &it.db)
+            .ok_or_else(|| AppError::AttachedDatabaseNotFound(origin.to_owned())),
+    }
+}
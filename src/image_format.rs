@@ -43,6 +43,12 @@ impl ImageFormatExt for ImageMeta {
     }
 }
 
+/// The format name (e.g. `jpeg`) known by `from_raw`
+pub fn format_name(s: &str) -> Option<&'static str> {
+    const NAMES: &[&str] = &["bmp", "gif", "hdr", "ico", "jpeg", "png", "pnm", "tga", "tiff", "webp"];
+    NAMES.iter().find(|it| **it == s).copied()
+}
+
 fn from_str(s: &str) -> ImageFormat {
     use ImageFormat::*;
     match s {
@@ -0,0 +1,66 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_derive::Serialize;
+
+
+
+/// `child` implies `parent` (e.g. `cat` implies `animal`)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Implication {
+    pub child: String,
+    pub parent: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Implications {
+    children: BTreeMap<String, BTreeSet<String>>,
+    parents: BTreeMap<String, BTreeSet<String>>,
+}
+
+
+impl Implications {
+    pub fn add(&mut self, child: &str, parent: &str) {
+        self.children.entry(parent.to_owned()).or_default().insert(child.to_owned());
+        self.parents.entry(child.to_owned()).or_default().insert(parent.to_owned());
+    }
+
+    /// The tag and the all tags implied by it
+    pub fn ancestors(&self, tag: &str) -> BTreeSet<String> {
+        walk(&self.parents, tag)
+    }
+
+    /// The tag and the all tags which imply it
+    pub fn descendants(&self, tag: &str) -> BTreeSet<String> {
+        walk(&self.children, tag)
+    }
+
+    /// Adding `child -> parent` makes a cycle
+    pub fn would_cycle(&self, child: &str, parent: &str) -> bool {
+        self.ancestors(parent).contains(child)
+    }
+}
+
+impl<'a> FromIterator<&'a Implication> for Implications {
+    fn from_iter<T: IntoIterator<Item = &'a Implication>>(iter: T) -> Self {
+        let mut result = Implications::default();
+        for it in iter {
+            result.add(&it.child, &it.parent);
+        }
+        result
+    }
+}
+
+
+fn walk(edges: &BTreeMap<String, BTreeSet<String>>, tag: &str) -> BTreeSet<String> {
+    let mut found = BTreeSet::new();
+    let mut stack = vec![tag.to_owned()];
+    while let Some(it) = stack.pop() {
+        if !found.insert(it.clone()) {
+            continue;
+        }
+        if let Some(next) = edges.get(&it) {
+            stack.extend(next.iter().filter(|it| !found.contains(*it)).cloned());
+        }
+    }
+    found
+}
@@ -1,17 +1,29 @@
 #[macro_use] pub mod database_macro;
 pub mod alias;
+pub mod archive;
 pub mod app;
 pub mod args;
+pub mod collection;
 pub mod database;
 pub mod defun;
 pub mod errors;
+pub mod event;
 pub mod expander;
 pub mod expression;
+pub mod federation;
 pub mod global_alias;
+pub mod implication;
 pub mod image_format;
 pub mod loader;
 pub mod meta;
+pub mod migration;
 pub mod output_format;
+pub mod rating;
+pub mod root;
+pub mod saved_search;
 pub mod search_history;
 pub mod server;
+pub mod synonym;
 pub mod tag;
+pub mod tag_stats;
+pub mod token;
@@ -122,18 +122,22 @@ impl<'a> Loader<'a> {
 
     fn generate_tags<T: AsRef<Path>>(&self, file: &T) -> AppResult<Vec<String>> {
         if_let_some!(tag_generator = self.config.tag_generator, Ok(vec!()));
-        let mut command = Command::new(tag_generator);
-        command.args(&[file.as_ref().as_os_str()]);
-        command.stdout(Stdio::piped());
-        let status = command.status()?;
-        if !status.success() {
-            let err = command.output()?.stderr;
-            let err = String::from_utf8(err)?;
-            return Err(AppError::TagGeneratorFailed(err));
-        }
-        let result = String::from_utf8(command.output()?.stdout)?;
-        Ok(result.lines().filter(|it| !it.is_empty()).map(ToOwned::to_owned).collect())
+        generate_tags(tag_generator, file)
+    }
+}
+
+pub fn generate_tags<T: AsRef<Path>>(tag_generator: &str, file: &T) -> AppResult<Vec<String>> {
+    let mut command = Command::new(tag_generator);
+    command.args(&[file.as_ref().as_os_str()]);
+    command.stdout(Stdio::piped());
+    let status = command.status()?;
+    if !status.success() {
+        let err = command.output()?.stderr;
+        let err = String::from_utf8(err)?;
+        return Err(AppError::TagGeneratorFailed(err));
     }
+    let result = String::from_utf8(command.output()?.stdout)?;
+    Ok(result.lines().filter(|it| !it.is_empty()).map(ToOwned::to_owned).collect())
 }
 
 fn has_image_extension<T: AsRef<Path>>(file: &T) -> AppResult<bool> {
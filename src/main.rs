@@ -7,21 +7,33 @@ use clap::ArgMatches;
 
 #[macro_use] pub mod database_macro;
 mod alias;
+mod archive;
 mod app;
 mod args;
+mod collection;
 mod database;
 mod defun;
 mod errors;
+mod event;
 mod expander;
 mod expression;
+mod federation;
 mod global_alias;
+mod implication;
 mod image_format;
 mod loader;
 mod meta;
+mod migration;
 mod output_format;
+mod rating;
+mod root;
+mod saved_search;
 mod search_history;
 mod server;
+mod synonym;
 mod tag;
+mod tag_stats;
+mod token;
 
 use crate::errors::{AppError, AppResult, AppResultU};
 
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+
+
+/// Schema change applied to the existing databases in order of `version`
+#[derive(Debug)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    /// `(table, column)` added by the migration.
+    /// The migration is skipped if it exists (e.g. the table was created by the newer `create_*_table.sql`).
+    pub added_column: Option<(&'static str, &'static str)>,
+    /// Rewrites or deletes the existing rows. The database file is backed up before it.
+    pub destructive: bool,
+    pub statements: &'static [&'static str],
+}
+
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Add namespace column to tags",
+        added_column: Some(("tags", "namespace")),
+        destructive: false,
+        statements: &[
+            "ALTER TABLE tags ADD COLUMN namespace TEXT",
+            include_str!("sql/update_tags_namespace.sql"),
+        ],
+    },
+    Migration {
+        version: 2,
+        description: "Add added column to tags",
+        added_column: Some(("tags", "added")),
+        destructive: false,
+        statements: &[
+            "ALTER TABLE tags ADD COLUMN added TEXT",
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "Add pinned and results columns to search_history",
+        added_column: Some(("search_history", "pinned")),
+        destructive: false,
+        statements: &[
+            "ALTER TABLE search_history ADD COLUMN pinned INTEGER DEFAULT 0",
+            "ALTER TABLE search_history ADD COLUMN results INTEGER",
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "Convert like/neutral/dislike tags into ratings",
+        added_column: Some(("images", "rating")),
+        destructive: true,
+        statements: &[
+            "ALTER TABLE images ADD COLUMN rating INTEGER",
+            "ALTER TABLE images ADD COLUMN views INTEGER DEFAULT 0",
+            "ALTER TABLE images ADD COLUMN viewed TEXT",
+            include_str!("sql/migrate_favorite_tags.sql"),
+            "DELETE FROM tags WHERE source = 'noir' AND favorite_rating(tag) IS NOT NULL",
+        ],
+    },
+    Migration {
+        version: 5,
+        description: "Replace token secrets with their hashes",
+        added_column: Some(("tokens", "hash")),
+        destructive: false,
+        statements: &[
+            "ALTER TABLE tokens ADD COLUMN hash TEXT",
+            "UPDATE tokens SET hash = hash_secret(token), token = NULL",
+            "CREATE UNIQUE INDEX IF NOT EXISTS tokens_hash ON tokens (hash)",
+        ],
+    },
+];
+
+
+/// Databases with a newer schema are not opened
+pub fn latest_version() -> u32 {
+    MIGRATIONS.iter().map(|it| it.version).max().unwrap_or(0)
+}
+
+/// Migrations newer than `version`
+pub fn pending(version: u32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS.iter().filter(move |it| version < it.version)
+}
+
+/// e.g. `default.sqlite` -> `default.sqlite.v3.bak`
+pub fn backup_path(db_file: &Path, version: u32) -> PathBuf {
+    let mut name = db_file.file_name().map(|it| it.to_owned()).unwrap_or_default();
+    name.push(format!(".v{}.bak", version));
+    db_file.with_file_name(name)
+}
@@ -6,6 +6,7 @@ use std::str::FromStr;
 use shell_escape::escape;
 
 use crate::errors::{AppError, AppResultU, AppResult};
+use crate::federation::FederatedMeta;
 use crate::meta::Meta;
 
 
@@ -24,13 +25,7 @@ impl OutputFormat {
         match self {
             Chrysoberyl => {
                 write!(w, "@push-image")?;
-                write!(w, " --meta width={}", meta.dimensions.width)?;
-                write!(w, " --meta height={}", meta.dimensions.width)?;
-                write!(w, " --meta format={}", meta.format)?;
-                if let Some(ref dhash) = &meta.dhash {
-                    write!(w, " --meta dhash={}", dhash)?;
-                }
-                writeln!(w, " {}", escape(Cow::from(&meta.file.path)))?;
+                write_chrysoberyl_meta(w, meta)?;
             },
             Json =>
                 writeln!(w, "{}", serde_json::to_string(meta)?)?,
@@ -41,6 +36,27 @@ impl OutputFormat {
         }
         Ok(())
     }
+
+    /// With the origin database of the federated search
+    pub fn write_federated<W: Write>(&self, w: &mut W, meta: &FederatedMeta) -> AppResultU {
+        use OutputFormat::*;
+
+        match self {
+            Chrysoberyl => {
+                write!(w, "@push-image --meta origin={}", escape(Cow::from(&meta.origin)))?;
+                write_chrysoberyl_meta(w, &meta.meta)?;
+            },
+            Json =>
+                writeln!(w, "{}", serde_json::to_string(meta)?)?,
+            PrettyJson =>
+                writeln!(w, "{}", serde_json::to_string_pretty(meta)?)?,
+            Simple => {
+                write!(w, "{}\t", meta.origin)?;
+                self.write(w, &meta.meta)?;
+            },
+        }
+        Ok(())
+    }
 }
 
 impl FromStr for OutputFormat {
@@ -59,3 +75,16 @@ impl FromStr for OutputFormat {
         Ok(result)
     }
 }
+
+
+/// The options and the path of `@push-image`
+fn write_chrysoberyl_meta<W: Write>(w: &mut W, meta: &Meta) -> AppResultU {
+    write!(w, " --meta width={}", meta.dimensions.width)?;
+    write!(w, " --meta height={}", meta.dimensions.height)?;
+    write!(w, " --meta format={}", meta.format)?;
+    if let Some(ref dhash) = &meta.dhash {
+        write!(w, " --meta dhash={}", dhash)?;
+    }
+    writeln!(w, " {}", escape(Cow::from(&meta.file.path)))?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use chrono::DateTime;
+use chrono::offset::Utc;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppResult};
+
+
+
+pub const MAX_RATING: u8 = 5;
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Score {
+    /// `0` to `MAX_RATING`, `None` means unrated
+    pub rating: Option<u8>,
+    pub views: u64,
+    pub viewed: Option<DateTime<Utc>>,
+}
+
+
+/// Rating of the legacy favorite tag (`like`, `neutral` and `dislike`)
+pub fn from_favorite(tag: &str) -> Option<u8> {
+    match tag {
+        "like" => Some(5),
+        "neutral" => Some(3),
+        "dislike" => Some(1),
+        _ => None,
+    }
+}
+
+pub fn validate(rating: u8) -> AppResult<u8> {
+    if rating <= MAX_RATING {
+        Ok(rating)
+    } else {
+        Err(AppError::InvalidRating(rating))
+    }
+}
@@ -0,0 +1,18 @@
+use chrono::DateTime;
+use chrono::offset::Utc;
+use serde_derive::Serialize;
+
+
+
+/// Registered library root, a named path prefix. Images keep their absolute paths,
+/// and belong to the longest root containing them. Moving a root relocates its images.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Root {
+    pub name: String,
+    pub path: String,
+    /// Number of images
+    pub images: u64,
+    /// Total file size of images
+    pub size: u64,
+    pub created: Option<DateTime<Utc>>,
+}
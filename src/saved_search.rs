@@ -0,0 +1,36 @@
+use chrono::DateTime;
+use chrono::offset::Utc;
+use serde_derive::{Deserialize, Serialize};
+
+
+
+/// Named expression usable as `@name` in expressions
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub expression: String,
+    pub description: Option<String>,
+    /// SQL `ORDER BY` terms (e.g. `views DESC`)
+    pub sort: Option<String>,
+    pub limit: Option<u64>,
+    /// Cached number of the results
+    #[serde(default)]
+    pub count: Option<u64>,
+    #[serde(default)]
+    pub counted: Option<DateTime<Utc>>,
+}
+
+
+impl SavedSearch {
+    /// Appends the sort order and the limit to the expanded expression
+    pub fn where_clause(&self, expanded: &str) -> String {
+        let mut result = format!("({})", expanded);
+        if let Some(ref sort) = self.sort {
+            result.push_str(&format!(" ORDER BY {}", sort));
+        }
+        if let Some(limit) = self.limit {
+            result.push_str(&format!(" LIMIT {}", limit));
+        }
+        result
+    }
+}
@@ -1,10 +1,42 @@
-
+use chrono::{DateTime, Duration};
+use chrono::offset::Utc;
+use jackdauer::duration;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::errors::{AppError, AppResult};
+
+
+
+/// Default number of the unpinned entries to keep
+pub const DEFAULT_MAX_SIZE: u64 = 1000;
 
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct SearchHistory {
     pub expression: String,
     pub uses: i64,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Number of the results at the last use
+    #[serde(default)]
+    pub results: Option<u64>,
+    #[serde(default)]
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct HistoryFilter {
+    /// Substring of the expression
+    pub q: Option<String>,
+    pub pinned: Option<bool>,
+    pub limit: Option<u64>,
+}
+
+
+/// The time `age` (e.g. `30 days`) ago
+pub fn older_than(age: &str) -> AppResult<DateTime<Utc>> {
+    let invalid = || AppError::InvalidDuration(age.to_owned());
+    let age = duration(age).map_err(|_| invalid())?;
+    let age = Duration::from_std(age).map_err(|_| invalid())?;
+    Utc::now().checked_sub_signed(age).ok_or_else(invalid)
 }
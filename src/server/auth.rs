@@ -0,0 +1,79 @@
+use std::sync::Mutex;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::{Error, web};
+use futures::future::LocalBoxFuture;
+
+use crate::database::Database;
+use crate::errors::{AppError, AppResultU};
+use crate::token::Scope;
+
+use super::AppData;
+
+
+
+/// Scopes required for the methods of a resource
+pub type Scopes = &'static [(&'static str, Scope)];
+
+pub const ADMIN: Scopes = &[("POST", Scope::Admin)];
+pub const READ: Scopes = &[("GET", Scope::Read)];
+pub const TAG: Scopes = &[("POST", Scope::Tag)];
+
+
+/// Scope required for the method. The unlisted methods require `Admin`.
+pub fn required_scope(scopes: Scopes, method: &Method) -> Scope {
+    scopes.iter().find(|(it, _)| *it == method.as_str()).map_or(Scope::Admin, |(_, scope)| *scope)
+}
+
+/// Middleware for `Resource::wrap_fn` to authorize the requests to the resource
+pub fn require<S>(scopes: Scopes) -> impl FnMut(ServiceRequest, &mut S) -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> + Clone
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error>,
+    S::Future: 'static,
+{
+    move |request: ServiceRequest, service: &mut S| -> LocalBoxFuture<'static, Result<ServiceResponse, Error>> {
+        let required = required_scope(scopes, request.method());
+        let response = check(&request, required).map(|_| service.call(request));
+        Box::pin(async move {
+            match response {
+                Ok(response) => response.await,
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+}
+
+pub fn authorize(db: &Database, secret: Option<&str>, required: Scope) -> AppResultU {
+    if !db.token_exists()? {
+        return Ok(());
+    }
+    let secret = secret.ok_or(AppError::Unauthorized)?;
+    let scopes = db.token_scopes(secret)?.ok_or(AppError::Unauthorized)?;
+    if scopes.iter().any(|it| it.allows(required)) {
+        return Ok(());
+    }
+    Err(AppError::Forbidden(required.as_str()))
+}
+
+/// From `Authorization: Bearer <token>` header or `token` query parameter (for `<img src>`)
+pub fn secret_of(request: &ServiceRequest) -> Option<String> {
+    let bearer = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|it| it.to_str().ok())
+        .and_then(|it| it.strip_prefix("Bearer "))
+        .map(|it| it.trim().to_owned());
+    if bearer.is_some() {
+        return bearer;
+    }
+    request.query_string()
+        .split('&')
+        .find_map(|it| it.strip_prefix("token="))
+        .map(ToOwned::to_owned)
+}
+
+fn check(request: &ServiceRequest, required: Scope) -> AppResultU {
+    let data: &web::Data<Mutex<AppData>> = request.app_data().ok_or(AppError::Standard("No app data"))?;
+    let data = data.lock().expect("lock auth");
+    authorize(&data.db, secret_of(request).as_deref(), required)
+}
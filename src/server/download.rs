@@ -1,108 +1,322 @@
+use std::borrow::ToOwned;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
-use std::collections::VecDeque;
-use std::sync::mpsc::{channel, Sender};
-use std::time::Duration;
-use std::{thread, time};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use curl::easy::{Easy as EasyCurl, WriteError};
+use chrono::DateTime;
+use chrono::offset::Utc;
+use curl::easy::{Easy as EasyCurl, List, WriteError};
+use if_let_return::if_let_some;
+use image::ImageFormat;
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 
 use crate::database::Database;
-use crate::errors::AppResultU;
+use crate::event::{Event, EventBus};
+use crate::errors::{AppError, AppResult, AppResultU, from_os_str, from_path};
+use crate::image_format::ImageFormatExt;
 use crate::loader;
+use crate::meta::Meta;
 use crate::tag::Tag;
 
+use super::hook::{HookError, Hooks};
+use super::util::{self, host_of};
+
 
 #[derive(Debug, Serialize)]
 pub struct Job {
+    /// Value of the `Cookie` header (e.g. `a=1; b=2`)
+    pub cookie: Option<String>,
+    pub headers: Option<HashMap<String, String>>,
+    pub hook_errors: Vec<HookError>,
+    pub referer: Option<String>,
     pub tags: Option<Tags>,
     pub to: PathBuf,
     pub url: String,
 }
 
+/// Row of the `queue` table
+#[derive(Debug, Serialize)]
+pub struct QueuedJob {
+    pub url: String,
+    pub created: Option<DateTime<Utc>>,
+    /// Failed hooks of the downloaded file. Empty if the download is not finished.
+    pub hook_errors: Vec<HookError>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Tags {
     pub items: Vec<String>,
     pub source: String
 }
 
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Number of concurrent transfers
+    pub workers: usize,
+    /// Maximum concurrent transfers for each host
+    pub per_host: usize,
+    /// Minimum interval between transfers starting for the same host
+    pub interval: Duration,
+    pub hooks: Hooks,
+}
+
 #[derive(Clone)]
 pub struct Manager {
-    tx: Sender<Job>
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    config: Config,
+    db: Mutex<Database>,
+    events: EventBus,
+    state: Mutex<State>,
+    wakeup: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    errors: usize,
+    hosts: HashMap<String, Host>,
+    pool: VecDeque<Job>,
+}
+
+#[derive(Default)]
+struct Host {
+    active: usize,
+    last_started: Option<Instant>,
+}
+
+enum Next {
+    Job(Job, String),
+    Wait(Option<Duration>),
+}
+
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            per_host: 1,
+            interval: Duration::from_secs(3),
+            hooks: Hooks::default(),
+        }
+    }
 }
 
 impl Manager {
-    pub fn new(db: Database) -> Self {
-        let (tx, rx) = channel::<Job>();
+    pub fn new(db: Database, config: Config, events: EventBus) -> Self {
+        let workers = config.workers.max(1);
+        let shared = Arc::new(Shared {
+            config,
+            db: Mutex::new(db),
+            events,
+            state: Mutex::new(State::default()),
+            wakeup: Condvar::new(),
+        });
+
+        for id in 0..workers {
+            let shared = shared.clone();
+            thread::spawn(move || shared.work(id));
+        }
 
-        thread::spawn(move || {
-            let mut pool: VecDeque<Job> = VecDeque::new();
-            let mut errors: usize = 0;
+        Self { shared }
+    }
 
-            loop {
-                let before = pool.len();
+    pub fn download(&self, job: Job) -> bool {
+        let mut state = match self.shared.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+        state.pool.push_back(job);
+        info!("Download: Queue: count={}", state.pool.len());
+        self.shared.wakeup.notify_all();
+        true
+    }
+}
 
-                while let Ok(job) = rx.try_recv() {
-                    pool.push_back(job);
-                }
+impl Shared {
+    fn work(&self, id: usize) {
+        loop {
+            let (mut job, host) = self.next_job();
 
-                let after = pool.len();
-                let delta = after - before;
-                if 0 < delta {
-                    info!("Download: Queue: count={} delta={}", after, delta);
-                }
+            info!("Download[{}]: {:?}", id, job);
+            let result = job.process(&self.db, &self.config.hooks, &self.events);
 
-                if let Some(job) = pool.pop_front() {
-                    info!("Download: {:?}", job);
-                    if let Err(err) = job.process(&db) {
-                        errors += 1;
-                        error!("Download: NG: {:?}", err);
-                        if let Ok(json) = serde_json::to_string(&job) {
-                            error!("NG-JOB: {}", json);
-                        } else {
-                            error!("NG-JOB: {:?}", job);
-                        }
+            let mut state = self.state.lock().expect("lock download state");
+            if let Some(it) = state.hosts.get_mut(&host) {
+                it.active -= 1;
+            }
+            match result {
+                Ok(path) => {
+                    info!("Download[{}]: OK: {:?}", id, job.url);
+                    let path = path.to_string_lossy().into_owned();
+                    self.events.publish(Event::DownloadCompleted { url: job.url.clone(), path });
+                }
+                Err(err) => {
+                    state.errors += 1;
+                    error!("Download[{}]: NG: {:?}", id, err);
+                    if let Ok(json) = serde_json::to_string(&job) {
+                        error!("NG-JOB: {}", json);
                     } else {
-                        info!("Download: OK: {:?}", job.url);
+                        error!("NG-JOB: {:?}", job);
                     }
-                    info!("Download: Queue: count={}, errors={}", pool.len(), errors);
+                    self.events.publish(Event::DownloadFailed { url: job.url.clone(), error: err.to_string() });
+                }
+            }
+            info!("Download: Queue: count={}, errors={}", state.pool.len(), state.errors);
+            self.wakeup.notify_all();
+        }
+    }
+
+    fn next_job(&self) -> (Job, String) {
+        let mut state = self.state.lock().expect("lock download state");
+        loop {
+            match state.take(&self.config, Instant::now()) {
+                Next::Job(job, host) => return (job, host),
+                Next::Wait(Some(timeout)) => {
+                    state = self.wakeup.wait_timeout(state, timeout).expect("wait download state").0;
                 }
+                Next::Wait(None) => {
+                    state = self.wakeup.wait(state).expect("wait download state");
+                }
+            }
+        }
+    }
+}
+
+impl State {
+    /// Takes the first job whose host is ready, or tells how long to wait for one
+    fn take(&mut self, config: &Config, now: Instant) -> Next {
+        let mut wait: Option<Duration> = None;
 
-                thread::sleep(time::Duration::from_secs(3));
+        for index in 0..self.pool.len() {
+            let host = host_of(&self.pool[index].url).unwrap_or_default();
+            let entry = self.hosts.entry(host.clone()).or_default();
+
+            if config.per_host <= entry.active {
+                continue;
             }
-        });
 
-        Self {tx}
+            if let Some(last_started) = entry.last_started {
+                let ready_at = last_started + config.interval;
+                if now < ready_at {
+                    let delta = ready_at - now;
+                    wait = Some(wait.map_or(delta, |it| it.min(delta)));
+                    continue;
+                }
+            }
+
+            entry.active += 1;
+            entry.last_started = Some(now);
+            let job = self.pool.remove(index).expect("index in pool");
+            return Next::Job(job, host);
+        }
+
+        self.hosts.retain(|_, it| 0 < it.active || it.last_started.map_or(false, |t| now < t + config.interval));
+
+        Next::Wait(wait)
     }
+}
 
-    pub fn download(&self, job: Job) -> bool {
-        self.tx.send(job).is_ok()
+impl QueuedJob {
+    pub fn list(db: &Database) -> AppResult<Vec<QueuedJob>> {
+        #[derive(Deserialize)]
+        struct Failures {
+            #[serde(default)]
+            hook_errors: Vec<HookError>,
+        }
+
+        let mut result = vec![];
+        for (url, job, created) in db.queued_jobs()? {
+            let failures: Failures = serde_json::from_str(&job)?;
+            result.push(QueuedJob { url, created, hook_errors: failures.hook_errors });
+        }
+        Ok(result)
     }
 }
 
 impl Job {
-    fn process(&self, db: &Database) -> AppResultU {
-        download(&self.url, &self.to)?;
-        write_record(db, self)?;
-        dequeue(db, &self.url)?;
-        Ok(())
+    fn process(&mut self, db: &Mutex<Database>, hooks: &Hooks, events: &EventBus) -> AppResult<PathBuf> {
+        let path = download(self, events)?;
+        let path = hooks.before_record(path, &mut self.hook_errors);
+        let path = write_record(&db.lock().expect("lock download db"), self, &path)?;
+        hooks.after_record(db, &path, &mut self.hook_errors);
+        let db = db.lock().expect("lock download db");
+        if self.hook_errors.is_empty() {
+            dequeue(&db, &self.url)?;
+        } else {
+            let json = serde_json::to_string(&self)?;
+            db.update_queue(&self.url, &json)?;
+            for it in &self.hook_errors {
+                events.publish(Event::HookFailed {
+                    url: self.url.clone(),
+                    path: path.to_string_lossy().into_owned(),
+                    hook: it.hook.clone(),
+                    message: it.message.clone(),
+                });
+            }
+        }
+        Ok(path)
+    }
+
+    /// `to` is a directory when it ends with a slash or already exists as a directory
+    fn to_directory(&self) -> bool {
+        self.to.is_dir() || self.to.as_os_str().to_str().map_or(false, |it| it.ends_with('/'))
     }
 }
 
-fn download<T: AsRef<Path>>(url: &str, download_to: T) -> AppResultU {
-    if let Some(parent) = download_to.as_ref().parent() {
-        fs::create_dir_all(parent)?;
+fn download(job: &Job, events: &EventBus) -> AppResult<PathBuf> {
+    let directory = if job.to_directory() {
+        job.to.clone()
+    } else {
+        job.to.parent().map(Path::to_path_buf).unwrap_or_default()
+    };
+    fs::create_dir_all(&directory)?;
+
+    let temp = temp_file_path(&directory);
+    let result = transfer(job, &temp, events).and_then(|(headers, effective_url)| {
+        let name = if job.to_directory() {
+            headers.iter().rev().find_map(|it| util::filename_from_content_disposition(it))
+                .or_else(|| effective_url.as_deref().and_then(util::filename_from_url))
+                .or_else(|| util::filename_from_url(&job.url))
+                .unwrap_or_else(|| "download".to_owned())
+        } else {
+            let name = job.to.file_name().ok_or(AppError::Standard("Invalid download path"))?;
+            from_os_str(name)?.to_owned()
+        };
+        let name = match sniff_format(&temp)? {
+            Some(format) => util::replace_extension(&name, format.to_str()),
+            None => name,
+        };
+        let path = unused_path(&directory, &name);
+        fs::rename(&temp, &path)?;
+        Ok(path)
+    });
+
+    if result.is_err() && temp.exists() {
+        if let Err(err) = fs::remove_file(&temp) {
+            error!("Failed to remove temporary file: {:?}: {:?}", temp, err);
+        }
     }
 
+    result
+}
+
+fn transfer(job: &Job, download_to: &Path, events: &EventBus) -> AppResult<(Vec<String>, Option<String>)> {
     let mut file = fs::OpenOptions::new()
         .read(false)
         .write(true).
         append(false)
         .create(true)
+        .truncate(true)
         .open(download_to)?;
 
     let mut curl = EasyCurl::new();
@@ -112,23 +326,80 @@ fn download<T: AsRef<Path>>(url: &str, download_to: T) -> AppResultU {
     curl.low_speed_time(Duration::from_secs(30))?;
     curl.low_speed_limit(1024)?;
     curl.http_version(curl::easy::HttpVersion::V11)?;
+    curl.follow_location(true)?;
+    curl.progress(true)?;
 
-    curl.url(url)?;
+    curl.url(&job.url)?;
 
-    curl.write_function(move |data| {
-        if let Err(err) = file.write_all(data) {
-            error!("Write error: {:?}", err);
-            return Err(WriteError::Pause);
+    if let Some(ref referer) = job.referer {
+        curl.referer(referer)?;
+    }
+    if let Some(ref cookie) = job.cookie {
+        curl.cookie(cookie)?;
+    }
+    if let Some(ref headers) = job.headers {
+        let mut list = List::new();
+        for (name, value) in headers {
+            list.append(&format!("{}: {}", name, value))?;
         }
-        Ok(data.len())
-    })?;
+        curl.http_headers(list)?;
+    }
 
-    curl.perform()?;
+    let mut headers = vec![];
+    let mut last_progress = Instant::now();
+    {
+        let mut transfer = curl.transfer();
+        transfer.progress_function(|total, downloaded, _, _| {
+            if Duration::from_secs(1) <= last_progress.elapsed() {
+                last_progress = Instant::now();
+                events.publish(Event::DownloadProgress {
+                    url: job.url.clone(),
+                    downloaded: downloaded as u64,
+                    total: total as u64,
+                });
+            }
+            true
+        })?;
+        transfer.header_function(|header| {
+            headers.push(String::from_utf8_lossy(header).into_owned());
+            true
+        })?;
+        transfer.write_function(|data| {
+            if let Err(err) = file.write_all(data) {
+                error!("Write error: {:?}", err);
+                return Err(WriteError::Pause);
+            }
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
 
-    let transfer = curl.transfer();
-    transfer.perform()?;
+    let code = curl.response_code()?;
+    if 400 <= code {
+        return Err(AppError::HttpStatus(code));
+    }
 
-    Ok(())
+    let effective_url = curl.effective_url()?.map(ToOwned::to_owned);
+    Ok((headers, effective_url))
+}
+
+pub fn sniff_format(file: &Path) -> AppResult<Option<ImageFormat>> {
+    let mut head = vec![];
+    fs::File::open(file)?.take(64).read_to_end(&mut head)?;
+    Ok(image::guess_format(&head).ok())
+}
+
+/// `name` in `directory`, numbered (e.g. `cat (1).png`) not to overwrite the existing file
+pub fn unused_path(directory: &Path, name: &str) -> PathBuf {
+    (0..).map(|n| directory.join(util::numbered_name(name, n))).find(|it| !it.exists()).expect("unused path")
+}
+
+fn temp_file_path(directory: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+    let mut path = directory.to_path_buf();
+    path.push(format!(".noir-{}-{}.part", process::id(), count));
+    path
 }
 
 fn dequeue(db: &Database, url: &str) -> AppResultU {
@@ -136,18 +407,46 @@ fn dequeue(db: &Database, url: &str) -> AppResultU {
     Ok(())
 }
 
-fn write_record(db: &Database, job: &Job) -> AppResultU {
-    let config = loader::Config { compute_dhash: true, ..Default::default() };
-    let mut loader = loader::Loader::new(db, config);
+fn write_record(db: &Database, job: &Job, path: &Path) -> AppResult<PathBuf> {
     let _tx = sql_retry!(db.transaction())?;
-    loader.load_file(&job.to)?;
+
+    let path = path.canonicalize()?;
+    let path = if let Some(existing) = find_duplicate(db, &path)? {
+        info!("Download: Duplicated: {:?} = {:?}", path, existing);
+        fs::remove_file(&path)?;
+        existing
+    } else {
+        let config = loader::Config { compute_dhash: true, ..Default::default() };
+        let mut loader = loader::Loader::new(db, config);
+        loader.load_file(&path)?;
+        path
+    };
+
     if let Some(ref tags) = job.tags {
         let mut _tags = vec![];
         for tag in &tags.items {
             _tags.push(Tag::from_str(&tag)?);
         }
-        let to = job.to.to_str().expect("job.to.to_str() was failed");
-        db.add_tags(to, &_tags, &tags.source)?;
+        db.add_tags(from_path(&path)?, &_tags, &tags.source)?;
     }
-    Ok(())
+    Ok(path)
+}
+
+fn find_duplicate(db: &Database, path: &Path) -> AppResult<Option<PathBuf>> {
+    let meta = Meta::from_file(&path, true)?;
+    if_let_some!(dhash = meta.dhash, Ok(None));
+    for candidate in db.find_by_dhash(&dhash)? {
+        let candidate = Path::new(&candidate.file.path);
+        if candidate != path && same_content(path, candidate)? {
+            return Ok(Some(candidate.to_path_buf()));
+        }
+    }
+    Ok(None)
+}
+
+fn same_content(a: &Path, b: &Path) -> AppResult<bool> {
+    if !b.is_file() || fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    Ok(fs::read(a)? == fs::read(b)?)
 }
@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use chrono::Local;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::database::Database;
+use crate::errors::{AppError, AppResult, AppResultU, from_os_str, from_path};
+use crate::image_format::ImageFormatExt;
+use crate::loader;
+use crate::tag::Tag;
+
+use super::download::{sniff_format, unused_path};
+use super::util;
+
+
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    /// Called with the path and JSON meta of the downloaded file
+    pub command: Option<String>,
+    pub convert: Vec<Conversion>,
+    /// `strftime` format of the subdirectory to move into
+    pub dated_directory: Option<String>,
+    pub tag_script: Option<String>,
+    pub tag_source: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Conversion {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HookError {
+    pub hook: String,
+    pub message: String,
+}
+
+
+impl Hooks {
+    /// Runs the hooks which modify the file before it is indexed, and returns the new path
+    pub fn before_record(&self, path: PathBuf, errors: &mut Vec<HookError>) -> PathBuf {
+        let path = match self.convert(&path) {
+            Ok(converted) => converted.unwrap_or(path),
+            Err(err) => {
+                record(errors, "convert", err);
+                path
+            }
+        };
+
+        match self.move_to_dated_directory(&path) {
+            Ok(moved) => moved.unwrap_or(path),
+            Err(err) => {
+                record(errors, "dated-directory", err);
+                path
+            }
+        }
+    }
+
+    /// Runs the hooks which need the indexed entry. `db` is locked only while it is accessed.
+    pub fn after_record(&self, db: &Mutex<Database>, path: &Path, errors: &mut Vec<HookError>) {
+        if let Err(err) = self.run_tag_script(db, path) {
+            record(errors, "tag-script", err);
+        }
+        if let Err(err) = self.run_command(db, path) {
+            record(errors, "command", err);
+        }
+    }
+
+    fn convert(&self, path: &Path) -> AppResult<Option<PathBuf>> {
+        if self.convert.is_empty() {
+            return Ok(None);
+        }
+
+        let format = sniff_format(path)?;
+        let format = match format {
+            Some(format) => format,
+            None => return Ok(None),
+        };
+
+        let format = format.to_str();
+        let conversion = self.convert.iter().find(|it| it.from == format);
+        let conversion = match conversion {
+            Some(conversion) => conversion,
+            None => return Ok(None),
+        };
+
+        let name = path.file_name().ok_or(AppError::Standard("Invalid download path"))?;
+        let name = util::replace_extension(from_os_str(name)?, &conversion.to);
+        let parent = path.parent().ok_or(AppError::Standard("Invalid download path"))?;
+        let converted = unused_path(parent, &name);
+
+        info!("Hook: convert: {:?} -> {:?}", path, converted);
+        image::open(path)?.save(&converted)?;
+        fs::remove_file(path)?;
+        Ok(Some(converted))
+    }
+
+    fn move_to_dated_directory(&self, path: &Path) -> AppResult<Option<PathBuf>> {
+        let format = match self.dated_directory {
+            Some(ref format) => format,
+            None => return Ok(None),
+        };
+
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Err(AppError::Standard("Invalid download path")),
+        };
+        let mut directory = parent.to_path_buf();
+        directory.push(Local::now().format(format).to_string());
+        fs::create_dir_all(&directory)?;
+        let moved = unused_path(&directory, from_os_str(name)?);
+
+        info!("Hook: move: {:?} -> {:?}", path, moved);
+        fs::rename(path, &moved)?;
+        Ok(Some(moved))
+    }
+
+    fn run_command(&self, db: &Mutex<Database>, path: &Path) -> AppResultU {
+        let command = match self.command {
+            Some(ref command) => command,
+            None => return Ok(()),
+        };
+
+        let meta = db.lock().expect("lock hook db").get(from_path(&path)?)?;
+        let meta = serde_json::to_string(&meta)?;
+
+        info!("Hook: command: {} {:?}", command, path);
+        let output = Command::new(command).arg(path).arg(meta).output()?;
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr).into_owned();
+            return Err(AppError::HookFailed(err));
+        }
+        Ok(())
+    }
+
+    fn run_tag_script(&self, db: &Mutex<Database>, path: &Path) -> AppResultU {
+        let tag_script = match self.tag_script {
+            Some(ref tag_script) => tag_script,
+            None => return Ok(()),
+        };
+
+        let tags = loader::generate_tags(tag_script, &path)?;
+        let tags: AppResult<Vec<Tag>> = tags.iter().map(|it| Tag::from_str(it)).collect();
+        let tag_source = self.tag_source.as_deref().unwrap_or("unknown");
+        db.lock().expect("lock hook db").add_tags(from_path(&path)?, &tags?, tag_source)?;
+        Ok(())
+    }
+}
+
+impl FromStr for Conversion {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        let (from, to) = s.split_once(':').ok_or_else(|| AppError::InvalidConversion(s.to_owned()))?;
+        Ok(Conversion { from: normalize_format(from), to: normalize_format(to) })
+    }
+}
+
+fn normalize_format(format: &str) -> String {
+    match &*format.to_lowercase() {
+        "jpg" => "jpeg".to_owned(),
+        "tif" => "tiff".to_owned(),
+        it => it.to_owned(),
+    }
+}
+
+fn record(errors: &mut Vec<HookError>, hook: &str, err: AppError) {
+    error!("Hook: {}: NG: {}", hook, err);
+    errors.push(HookError { hook: hook.to_owned(), message: err.to_string() });
+}
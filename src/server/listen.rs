@@ -0,0 +1,84 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use crate::errors::{AppError, AppResult};
+
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Listen {
+    /// `host:port`
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+
+impl Listen {
+    /// Parses `unix:/path/to/socket`, `address:port` or `address` (with the default port)
+    pub fn parse(s: &str, default_port: u16) -> AppResult<Self> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err(AppError::InvalidBindAddress(s.to_owned()));
+            }
+            return Ok(Listen::Unix(PathBuf::from(path)));
+        }
+
+        if s.parse::<SocketAddr>().is_ok() {
+            return Ok(Listen::Tcp(s.to_owned()));
+        }
+
+        let ip = s.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = ip.parse::<IpAddr>() {
+            return Ok(Listen::Tcp(SocketAddr::new(ip, default_port).to_string()));
+        }
+
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(Listen::Tcp(s.to_owned())),
+            Some(_) => Err(AppError::InvalidBindAddress(s.to_owned())),
+            None if s.is_empty() => Err(AppError::InvalidBindAddress(s.to_owned())),
+            None => Ok(Listen::Tcp(format!("{}:{}", s, default_port))),
+        }
+    }
+}
+
+impl Listen {
+    /// Reachable only from this machine (loopback address, `localhost` or Unix domain socket)
+    pub fn is_local(&self) -> bool {
+        match self {
+            Listen::Tcp(address) => {
+                if let Ok(address) = address.parse::<SocketAddr>() {
+                    return address.ip().is_loopback();
+                }
+                address.rsplit_once(':').map_or(false, |(host, _)| host.eq_ignore_ascii_case("localhost"))
+            },
+            Listen::Unix(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listen::Tcp(address) => write!(f, "{}", address),
+            Listen::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Removes the socket file left by the previous process.
+/// The socket of a live server (which accepts the connection) is kept, so the bind fails.
+#[cfg(unix)]
+pub fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::io::ErrorKind;
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => match UnixStream::connect(path) {
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => std::fs::remove_file(path),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
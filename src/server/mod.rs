@@ -1,5 +1,7 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
+use std::iter::once;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Mutex;
@@ -7,35 +9,72 @@ use std::sync::Mutex;
 use actix_cors::Cors;
 use actix_files::Files;
 use actix_web::middleware::Logger;
+use actix_web::web::Bytes;
 use actix_web::{App, HttpResponse, HttpServer, http, web};
-use log::{info, error};
+use futures::StreamExt;
+use log::{info, error, warn};
 use logging_timer::{timer, executing, Level};
 use serde::{Deserialize, Serialize};
 
 use crate::alias::Alias;
+use crate::collection;
 use crate::database::Database;
 use crate::errors::{AppError, AppResult};
+use crate::event::EventBus;
 use crate::expander::Expander;
 use crate::expression::modifier::replace_tag;
 use crate::expression::parser::parse;
+use crate::federation::{self, Attached, FederatedMeta};
 use crate::global_alias::GlobalAliasTable;
 use crate::meta::Meta;
-use crate::search_history::SearchHistory;
-use crate::tag::Tag;
-
+use crate::rating;
+use crate::saved_search::SavedSearch;
+use crate::search_history::{self, HistoryFilter, SearchHistory};
+use crate::tag::{GroupedTags, Tag, TagOperation};
+use crate::tag_stats;
+use crate::token::Scope;
+use auth::{ADMIN, READ, TAG, require};
+use listen::Listen;
+
+pub mod auth;
 pub mod download;
+pub mod hook;
+pub mod listen;
 pub mod util;
 
 
+/// `Logger::default()` without the query string, which may contain `token`
+const LOG_FORMAT: &str = r#"%a "%U" %s %b "%{User-Agent}i" %T"#;
+
+
+pub struct Config {
+    /// No cross-origin access if empty
+    pub cors_origins: Vec<String>,
+    pub download_to: Option<String>,
+    pub listens: Vec<Listen>,
+    pub root: String,
+}
+
 pub struct AppData {
     pub aliases: GlobalAliasTable,
+    pub attached: Vec<Attached>,
     pub db: Database,
     pub dl_manager:  download::Manager,
     pub download_to: Option<String>,
+    pub events: EventBus,
+}
+
+impl AppData {
+    /// Database of the `origin` of the federated search result
+    fn database(&self, origin: Option<&str>) -> AppResult<&Database> {
+        federation::database(&self.db, &self.attached, origin)
+    }
 }
 
 #[derive(Deserialize)]
 struct FileQuery {
+    /// `origin` of the federated search result (Default: main)
+    origin: Option<String>,
     path: String
 }
 
@@ -47,13 +86,19 @@ struct Favorite {
 
 #[derive(Deserialize)]
 struct FileTagsQuery {
+    /// Group the tags by namespace
+    grouped: Option<bool>,
+    /// `origin` of the federated search result (Default: main)
+    origin: Option<String>,
     path: String
 }
 
 #[derive(Deserialize)]
 struct SearchQuery {
     expression: String,
-    record: Option<bool>
+    record: Option<bool>,
+    /// Search the attached databases too
+    federated: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -62,52 +107,153 @@ struct QueryResult {
     expression: String,
 }
 
+#[derive(Serialize)]
+struct FederatedResult {
+    items: Vec<FederatedMeta>,
+}
+
 #[derive(Deserialize)]
 struct DownloadRequest {
+    /// Value of the `Cookie` header
+    cookie: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    referer: Option<String>,
     tags: Option<download::Tags>,
-    to: String,
+    /// Relative path to `download-to`. If it ends with `/`, the file name is detected from the response.
+    to: Option<String>,
     url: String,
 }
 
+#[derive(Deserialize)]
+struct ClearTagsRequest {
+    path: String,
+    source: String,
+}
+
 #[derive(Deserialize)]
 struct ExpressionReplaceTag {
     expression: String,
     tag: String
 }
 
+#[derive(Deserialize)]
+struct BatchTagRequest {
+    dry_run: Option<bool>,
+    /// Expression to select the target paths instead of `paths`
+    expression: Option<String>,
+    operation: TagOperation,
+    paths: Option<Vec<String>>,
+    tags: download::Tags,
+}
+
+#[derive(Deserialize)]
+struct CollectionItemsRequest {
+    /// Position to insert (Default: the end)
+    at: Option<usize>,
+    paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CollectionPathsRequest {
+    paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CollectionRequest {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct CompleteQuery {
+    limit: Option<usize>,
+    q: String,
+}
+
+#[derive(Deserialize)]
+struct CooccurrenceQuery {
+    limit: Option<usize>,
+    tag: String,
+}
+
+#[derive(Deserialize)]
+struct DropTagRequest {
+    source: Option<String>,
+    tag: String,
+}
+
+#[derive(Deserialize)]
+struct HistoryClearRequest {
+    /// e.g. `30 days`
+    older_than: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HistoryPinRequest {
+    expression: String,
+    pinned: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct HistoryRequest {
+    expression: String,
+}
+
+#[derive(Deserialize)]
+struct ImplicationRequest {
+    child: String,
+    parent: String,
+}
+
+#[derive(Deserialize)]
+struct MergeTagsRequest {
+    from: Vec<String>,
+    into: String,
+    source: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RatingRequest {
+    path: String,
+    /// `null` to clear
+    rating: Option<u8>,
+}
+
+#[derive(Deserialize)]
+struct RenameTagRequest {
+    from: String,
+    source: Option<String>,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct SynonymRequest {
+    alias: String,
+    canonical: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TagStatsQuery {
+    source: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct SetTagRequest {
     path: String,
     tags: download::Tags,
 }
 
-fn update_favorite(
-    data: web::Data<Mutex<AppData>>,
-    favorite: web::Query<Favorite>,
-    tag_to_add: &'static str,
-    tags_to_delete: &'static[&'static str]
-) -> AppResult<HttpResponse> {
+fn update_favorite(data: web::Data<Mutex<AppData>>, favorite: web::Query<Favorite>, tag: &'static str) -> AppResult<HttpResponse> {
     let data = data.lock().expect("lock file");
-
-    {
-        let mut tags = vec![];
-        for tag in tags_to_delete {
-            tags.push(Tag::from_str(tag)?);
-        }
-        data.db.delete_tags(&favorite.path, &tags, "noir")?;
-    }
+    let rating = rating::from_favorite(tag);
 
     if let Some(toggle) = favorite.toggle {
-        if toggle && data.db.tag_exists(&favorite.path, tag_to_add)? {
-            let tags = [Tag::from_str(tag_to_add)?];
-            data.db.delete_tags(&favorite.path, &tags, "noir")?;
+        if toggle && data.db.score(&favorite.path)?.rating == rating {
+            data.db.set_rating(&favorite.path, None)?;
             return Ok(HttpResponse::Ok().json(false))
         }
     }
 
-    let tags = [Tag::from_str(tag_to_add)?];
-    data.db.add_tags(&favorite.path, &tags, "noir")?;
-
+    data.db.set_rating(&favorite.path, rating)?;
     Ok(HttpResponse::Ok().json(true))
 }
 
@@ -139,15 +285,29 @@ async fn on_aliases(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse>
     Ok(HttpResponse::Ok().json(aliases))
 }
 
+/// Origin names for the federated search
+async fn on_databases(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock databases");
+    let names: Vec<&str> = once(federation::MAIN).chain(data.attached.iter().map(|it| it.name.as_str())).collect();
+    Ok(HttpResponse::Ok().json(names))
+}
+
 async fn on_download(data: web::Data<Mutex<AppData>>, request: web::Json<DownloadRequest>) -> AppResult<HttpResponse> {
     let data = data.lock().expect("lock downlod");
 
     if let Some(download_to) = &data.download_to {
+        let suffix = request.to.as_deref().unwrap_or("");
+        if !util::is_inner_path(suffix) {
+            return Err(AppError::Standard("`to` must be a relative path without `..`"));
+        }
         let mut to = Path::new(&download_to).to_path_buf();
-        let suffix = util::shorten_path(&request.to);
-        to.push(&suffix);
+        to.push(util::shorten_path(suffix));
 
         let job = download::Job {
+            cookie: request.cookie.clone(),
+            headers: request.headers.clone(),
+            hook_errors: vec![],
+            referer: request.referer.clone(),
             to,
             tags: request.tags.clone(),
             url: request.url.clone(),
@@ -168,8 +328,124 @@ async fn on_download(data: web::Data<Mutex<AppData>>, request: web::Json<Downloa
     Err(AppError::Standard("Server option `download-to` is not given"))
 }
 
+async fn on_batch_tags(data: web::Data<Mutex<AppData>>, request: web::Json<BatchTagRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock batch tags");
+
+    let paths = match (&request.expression, &request.paths) {
+        (Some(expression), None) => {
+            let expander = Expander::generate(&data.db, &data.aliases)?;
+            let raw = expander.expand_str(expression)?;
+            data.db.select_paths(raw.as_ref()).map_err(|it| it.in_expression(expression))?
+        },
+        (None, Some(paths)) => paths.clone(),
+        _ => return Err(AppError::Standard("Either `expression` or `paths` is required")),
+    };
+
+    let mut tags = vec![];
+    for tag in &request.tags.items {
+        tags.push(Tag::from_str(tag)?);
+    }
+
+    let result = data.db.batch_tags(request.operation, &paths, &tags, &request.tags.source, request.dry_run.unwrap_or(false))?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn on_clear_tags(data: web::Data<Mutex<AppData>>, request: web::Json<ClearTagsRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock clear_tags");
+    let _tx = data.db.transaction()?;
+    data.db.clear_tags(&request.path, &request.source)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_collection(data: web::Data<Mutex<AppData>>, name: web::Path<String>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collection");
+    let mut items: Vec<Meta> = vec![];
+    data.db.select_collection(&name, |meta| {
+        items.push(meta.clone());
+        Ok(())
+    })?;
+    Ok(HttpResponse::Ok().json(items))
+}
+
+async fn on_collection_add(data: web::Data<Mutex<AppData>>, name: web::Path<String>, request: web::Json<CollectionItemsRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collection_add");
+    let mut items = data.db.collection_paths(&name)?;
+    collection::insert(&mut items, &request.paths, request.at);
+    data.db.set_collection_paths(&name, &items)?;
+    Ok(HttpResponse::Ok().json(items))
+}
+
+async fn on_collection_create(data: web::Data<Mutex<AppData>>, request: web::Json<CollectionRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collection_create");
+    data.db.create_collection(&request.name)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_collection_delete(data: web::Data<Mutex<AppData>>, name: web::Path<String>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collection_delete");
+    let deleted = data.db.delete_collection(&name)?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+async fn on_collection_remove(data: web::Data<Mutex<AppData>>, name: web::Path<String>, request: web::Json<CollectionPathsRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collection_remove");
+    let mut items = data.db.collection_paths(&name)?;
+    collection::remove(&mut items, &request.paths);
+    data.db.set_collection_paths(&name, &items)?;
+    Ok(HttpResponse::Ok().json(items))
+}
+
+async fn on_collection_replace(data: web::Data<Mutex<AppData>>, name: web::Path<String>, request: web::Json<CollectionPathsRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collection_replace");
+    let mut items = vec![];
+    collection::insert(&mut items, &request.paths, None);
+    data.db.set_collection_paths(&name, &items)?;
+    Ok(HttpResponse::Ok().json(items))
+}
+
+async fn on_collections(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock collections");
+    let collections = data.db.collections()?;
+    Ok(HttpResponse::Ok().json(collections))
+}
+
+async fn on_complete_tags(data: web::Data<Mutex<AppData>>, query: web::Query<CompleteQuery>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock complete_tags");
+    let counts = data.db.tag_counts(None)?;
+    let found = tag_stats::complete(&counts, &query.q, query.limit.unwrap_or(20));
+    Ok(HttpResponse::Ok().json(found))
+}
+
+async fn on_cooccurrences(data: web::Data<Mutex<AppData>>, query: web::Query<CooccurrenceQuery>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock cooccurrences");
+    let found = data.db.tag_cooccurrences(&query.tag, query.limit.unwrap_or(20))?;
+    Ok(HttpResponse::Ok().json(found))
+}
+
 async fn on_dislike(data: web::Data<Mutex<AppData>>, favorite: web::Query<Favorite>) -> AppResult<HttpResponse> {
-    update_favorite(data, favorite, "dislike", &["like", "neutral"])
+    update_favorite(data, favorite, "dislike")
+}
+
+async fn on_events(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock events");
+    let stream = data.events.subscribe().map(|event| {
+        let json = serde_json::to_string(&event)?;
+        Ok::<_, AppError>(Bytes::from(format!("data: {}\n\n", json)))
+    });
+    Ok(
+        HttpResponse::Ok()
+        .header("Cache-Control", "no-cache")
+        .content_type("text/event-stream")
+        .streaming(stream)
+    )
+}
+
+async fn on_drop_tag(data: web::Data<Mutex<AppData>>, request: web::Json<DropTagRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock drop_tag");
+    let tag = Tag::from_str(&request.tag)?;
+    let _tx = data.db.transaction()?;
+    let deleted = data.db.drop_tag(&tag, request.source.as_deref())?;
+    Ok(HttpResponse::Ok().json(deleted))
 }
 
 async fn on_expression_replace_tag(query: web::Json<ExpressionReplaceTag>) -> AppResult<HttpResponse> {
@@ -182,10 +458,11 @@ async fn on_file(data: web::Data<Mutex<AppData>>, query: web::Query<FileQuery>)
     let timer = timer!(Level::Info; "on_file_tags");
 
     let data = data.lock().expect("lock file");
+    let db = data.database(query.origin.as_deref())?;
 
     executing!(timer, "Get meta from database: path={}", query.path);
-    let found = data.db.get(&query.path)?;
-    let found = found.ok_or(AppError::Void)?;
+    let found = db.get(&query.path)?;
+    let found = found.ok_or_else(|| AppError::PathNotFound(query.path.clone()))?;
 
     let mut content: Vec<u8> = vec![];
     executing!(timer, "Read file: path={}", query.path);
@@ -204,23 +481,178 @@ async fn on_file(data: web::Data<Mutex<AppData>>, query: web::Query<FileQuery>)
 async fn on_file_tags(data: web::Data<Mutex<AppData>>, query: web::Query<FileTagsQuery>) -> AppResult<HttpResponse> {
     let _timer = timer!(Level::Info; "on_file_tags");
     let data = data.lock().expect("lock file tags");
-    let tags = data.db.tags_by_path(&query.path)?;
+    let tags = data.database(query.origin.as_deref())?.tags_by_path(&query.path)?;
     info!("on_file_tags: file={:?}, tags={:?}", query.path, tags);
+    if query.grouped.unwrap_or(false) {
+        let grouped: GroupedTags = tags.iter().map(String::as_str).collect();
+        return Ok(HttpResponse::Ok().json(grouped));
+    }
     Ok(HttpResponse::Ok().json(tags))
 }
 
-async fn on_history(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+async fn on_history(data: web::Data<Mutex<AppData>>, query: web::Query<HistoryFilter>) -> AppResult<HttpResponse> {
     let data = data.lock().expect("lock search");
-    let history: Vec<SearchHistory> = data.db.search_history()?;
+    let history: Vec<SearchHistory> = data.db.search_history(&query)?;
     Ok(HttpResponse::Ok().json(history))
 }
 
+async fn on_history_clear(data: web::Data<Mutex<AppData>>, request: web::Json<HistoryClearRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock history_clear");
+    let before = request.older_than.as_deref().map(search_history::older_than).transpose()?;
+    let deleted = data.db.clear_search_history(before)?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+async fn on_history_delete(data: web::Data<Mutex<AppData>>, request: web::Json<HistoryRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock history_delete");
+    data.db.delete_search_history(&request.expression)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_history_pin(data: web::Data<Mutex<AppData>>, request: web::Json<HistoryPinRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock history_pin");
+    data.db.set_history_pinned(&request.expression, request.pinned.unwrap_or(true))?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_implication_add(data: web::Data<Mutex<AppData>>, request: web::Json<ImplicationRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock implication_add");
+    let child = Tag::from_str(&request.child)?;
+    let parent = Tag::from_str(&request.parent)?;
+    data.db.add_implication(&child, &parent)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_implication_delete(data: web::Data<Mutex<AppData>>, request: web::Json<ImplicationRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock implication_delete");
+    let deleted = data.db.delete_implication(&request.child, &request.parent)?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+async fn on_implications(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock implications");
+    let implications = data.db.implications()?;
+    Ok(HttpResponse::Ok().json(implications))
+}
+
 async fn on_like(data: web::Data<Mutex<AppData>>, favorite: web::Query<Favorite>) -> AppResult<HttpResponse> {
-    update_favorite(data, favorite, "like", &["dislike", "neutral"])
+    update_favorite(data, favorite, "like")
+}
+
+async fn on_merge_tags(data: web::Data<Mutex<AppData>>, request: web::Json<MergeTagsRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock merge_tags");
+    let mut tags = vec![];
+    for tag in &request.from {
+        tags.push(Tag::from_str(tag)?);
+    }
+    let into = Tag::from_str(&request.into)?;
+    let _tx = data.db.transaction()?;
+    let merged = data.db.merge_tags(&tags, &into, request.source.as_deref())?;
+    Ok(HttpResponse::Ok().json(merged))
+}
+
+async fn on_namespaces(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock namespaces");
+    let namespaces = data.db.namespaces()?;
+    Ok(HttpResponse::Ok().json(namespaces))
 }
 
 async fn on_neutral(data: web::Data<Mutex<AppData>>, favorite: web::Query<Favorite>) -> AppResult<HttpResponse> {
-    update_favorite(data, favorite, "neutral", &["like", "dislike"])
+    update_favorite(data, favorite, "neutral")
+}
+
+async fn on_queue(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock queue");
+    Ok(HttpResponse::Ok().json(download::QueuedJob::list(&data.db)?))
+}
+
+async fn on_rating(data: web::Data<Mutex<AppData>>, request: web::Json<RatingRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock rating");
+    data.db.set_rating(&request.path, request.rating)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_remove_tags(data: web::Data<Mutex<AppData>>, request: web::Json<SetTagRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock remove_tags");
+    let mut tags = vec![];
+    for tag in &request.tags.items {
+        tags.push(Tag::from_str(tag)?);
+    }
+    let _tx = data.db.transaction()?;
+    data.db.delete_tags(&request.path, &tags, &request.tags.source)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_rename_tag(data: web::Data<Mutex<AppData>>, request: web::Json<RenameTagRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock rename_tag");
+    let from = Tag::from_str(&request.from)?;
+    let to = Tag::from_str(&request.to)?;
+    let _tx = data.db.transaction()?;
+    let renamed = data.db.rename_tag(&from, &to, request.source.as_deref())?;
+    Ok(HttpResponse::Ok().json(renamed))
+}
+
+async fn on_replace_tags(data: web::Data<Mutex<AppData>>, request: web::Json<SetTagRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock replace_tags");
+    let mut tags = vec![];
+    for tag in &request.tags.items {
+        tags.push(Tag::from_str(tag)?);
+    }
+    let _tx = data.db.transaction()?;
+    data.db.set_tags(&request.path, &tags, &request.tags.source)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_roots(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock roots");
+    Ok(HttpResponse::Ok().json(data.db.roots()?))
+}
+
+async fn on_saved(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock saved");
+    let mut saved: Vec<SavedSearch> = data.db.saved_searches()?.into_values().collect();
+    saved.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(HttpResponse::Ok().json(saved))
+}
+
+async fn on_saved_delete(data: web::Data<Mutex<AppData>>, name: web::Path<String>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock saved_delete");
+    let deleted = data.db.delete_saved_search(&name)?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+async fn on_saved_refresh(data: web::Data<Mutex<AppData>>, name: web::Path<String>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock saved_refresh");
+    let expander = Expander::generate(&data.db, &data.aliases)?;
+    let count = data.db.select_paths(&expander.expand_saved_search(&name)?)?.len() as u64;
+    data.db.set_saved_search_count(&name, count)?;
+    Ok(HttpResponse::Ok().json(count))
+}
+
+async fn on_saved_search(data: web::Data<Mutex<AppData>>, name: web::Path<String>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock saved_search");
+    let expander = Expander::generate(&data.db, &data.aliases)?;
+    let expression = expander.expand_saved_search(&name)?;
+    let mut items: Vec<Meta> = vec![];
+    data.db.select(&expression, false, |meta, _vacuumed| {
+        items.push(meta.clone());
+        Ok(())
+    })?;
+    Ok(HttpResponse::Ok().json(QueryResult { items, expression }))
+}
+
+async fn on_saved_update(data: web::Data<Mutex<AppData>>, saved: web::Json<SavedSearch>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock saved_update");
+    let expander = Expander::generate(&data.db, &data.aliases)?;
+    expander.expand_str(&saved.expression)?;
+    data.db.upsert_saved_search(&saved)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_score(data: web::Data<Mutex<AppData>>, query: web::Query<FileQuery>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock score");
+    let score = data.database(query.origin.as_deref())?.score(&query.path)?;
+    Ok(HttpResponse::Ok().json(score))
 }
 
 async fn on_search(data: web::Data<Mutex<AppData>>, query: web::Json<SearchQuery>) -> AppResult<HttpResponse> {
@@ -228,6 +660,19 @@ async fn on_search(data: web::Data<Mutex<AppData>>, query: web::Json<SearchQuery
 
     let data = data.lock().expect("lock search");
 
+    if query.federated.unwrap_or(false) {
+        executing!(timer, "Federated search: {}", &query.expression);
+        let mut items: Vec<FederatedMeta> = vec![];
+        federation::search(&data.db, &data.attached, &data.aliases, &query.expression, |origin, meta| {
+            items.push(FederatedMeta { origin: origin.to_owned(), meta: meta.clone() });
+            Ok(())
+        })?;
+        if query.record.unwrap_or(false) {
+            data.db.add_search_history(&query.expression, Some(items.len() as u64))?;
+        }
+        return Ok(HttpResponse::Ok().json(FederatedResult { items }));
+    }
+
     executing!(timer, "Expand: {}", &query.expression);
     let expander = Expander::generate(&data.db, &data.aliases)?;
     let expression = expander.expand_str(&query.expression)?;
@@ -238,11 +683,11 @@ async fn on_search(data: web::Data<Mutex<AppData>>, query: web::Json<SearchQuery
     data.db.select(expression.as_ref(), false, |meta, _vacuumed| {
         items.push(meta.clone());
         Ok(())
-    })?;
+    }).map_err(|it| it.in_expression(&query.expression))?;
 
     executing!(timer, "Add history: {}", &query.expression);
     if query.record.unwrap_or(false) {
-        data.db.add_search_history(&query.expression)?;
+        data.db.add_search_history(&query.expression, Some(items.len() as u64))?;
     }
 
     Ok(HttpResponse::Ok().json(QueryResult { items, expression: expression.to_string() }))
@@ -254,58 +699,192 @@ async fn on_set_tags(data: web::Data<Mutex<AppData>>, request: web::Json<SetTagR
     for tag in &request.tags.items {
         tags.push(Tag::from_str(&tag)?);
     }
+    let _tx = data.db.transaction()?;
     data.db.add_tags(&request.path, &tags, &request.tags.source)?;
     Ok(HttpResponse::Ok().json(true))
 }
 
+async fn on_sources(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock sources");
+    let sources = data.db.sources()?;
+    Ok(HttpResponse::Ok().json(sources))
+}
+
+async fn on_synonym_add(data: web::Data<Mutex<AppData>>, request: web::Json<SynonymRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock synonym_add");
+    let alias = Tag::from_str(&request.alias)?;
+    let canonical = request.canonical.as_deref().ok_or(AppError::Standard("`canonical` is required"))?;
+    let canonical = Tag::from_str(canonical)?;
+    data.db.add_synonym(&alias, &canonical)?;
+    Ok(HttpResponse::Ok().json(true))
+}
+
+async fn on_synonym_delete(data: web::Data<Mutex<AppData>>, request: web::Json<SynonymRequest>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock synonym_delete");
+    let deleted = data.db.delete_synonym(&request.alias)?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+async fn on_synonyms(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock synonyms");
+    let synonyms = data.db.synonyms()?;
+    Ok(HttpResponse::Ok().json(synonyms))
+}
+
+async fn on_tag_stats(data: web::Data<Mutex<AppData>>, query: web::Query<TagStatsQuery>) -> AppResult<HttpResponse> {
+    let data = data.lock().expect("lock tag_stats");
+    let counts = data.db.tag_counts(query.source.as_deref())?;
+    Ok(HttpResponse::Ok().json(counts))
+}
+
 async fn on_tags(data: web::Data<Mutex<AppData>>) -> AppResult<HttpResponse> {
     let data = data.lock().expect("lock search");
     let tags: Vec<String> = data.db.tags()?;
     Ok(HttpResponse::Ok().json(tags))
 }
 
+async fn on_view(data: web::Data<Mutex<AppData>>, query: web::Query<FileQuery>) -> AppResult<HttpResponse> {
+    if query.origin.as_deref().map_or(false, |it| it != federation::MAIN) {
+        return Err(AppError::Standard("The attached databases are read-only"));
+    }
+    let data = data.lock().expect("lock view");
+    let views = data.db.add_view(&query.path)?;
+    Ok(HttpResponse::Ok().json(views))
+}
+
 #[actix_web::main]
 pub async fn start(
     db: Database,
+    attached: Vec<Attached>,
     dl_manager: download::Manager,
     aliases: GlobalAliasTable,
-    port: u16,
-    root: String,
-    download_to: Option<String>
+    events: EventBus,
+    config: Config,
 ) -> std::io::Result<()> {
 
-    let app_data = AppData { aliases, dl_manager, db, download_to };
+    if !db.token_exists().unwrap_or(false) {
+        warn!("No tokens are registered, so the server does not require authentication from this machine");
+    }
+
+    let Config { cors_origins, download_to, listens, root } = config;
+    let app_data = AppData { aliases, attached, dl_manager, db, download_to, events };
     let data = web::Data::new(Mutex::new(app_data));
 
-    HttpServer::new(move || {
-        let cors = Cors::default()
-             .allow_any_origin()
-             .allowed_methods(vec!["GET", "POST", "DELETE"])
+    let mut server = HttpServer::new(move || {
+        let mut cors = Cors::default()
+             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
              .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
              .allowed_header(http::header::CONTENT_TYPE)
              .max_age(3600);
+        for origin in &cors_origins {
+            cors = cors.allowed_origin(origin);
+        }
         App::new()
-            .wrap(Logger::default())
+            .wrap(Logger::new(LOG_FORMAT))
             .wrap(cors)
             .app_data(data.clone())
-            .service(web::resource("/alias/{name}")
+            .service(
+                web::resource("/alias/{name}")
                 .route(web::get().to(on_alias))
                 .route(web::delete().to(on_alias_delete))
-                .route(web::post().to(on_alias_update)))
-            .service(web::resource("/aliases").route(web::get().to(on_aliases)))
-            .service(web::resource("/download").route(web::post().to(on_download)))
-            .service(web::resource("/like").route(web::post().to(on_like)))
-            .service(web::resource("/dislike").route(web::post().to(on_dislike)))
-            .service(web::resource("/neutral").route(web::post().to(on_neutral)))
-            .service(web::resource("/file").route(web::get().to(on_file)))
-            .service(web::resource("/file/tags").route(web::get().to(on_file_tags)))
-            .service(web::resource("/history").route(web::get().to(on_history)))
-            .service(web::resource("/search").route(web::post().to(on_search)))
-            .service(web::resource("/expression/replace_tag").route(web::post().to(on_expression_replace_tag)))
+                .route(web::post().to(on_alias_update))
+                .wrap_fn(require(&[("GET", Scope::Read), ("DELETE", Scope::AliasAdmin), ("POST", Scope::AliasAdmin)])))
+            .service(web::resource("/aliases").route(web::get().to(on_aliases)).wrap_fn(require(READ)))
+            .service(
+                web::resource("/collections")
+                .route(web::get().to(on_collections))
+                .route(web::post().to(on_collection_create))
+                .wrap_fn(require(&[("GET", Scope::Read), ("POST", Scope::Tag)])))
+            .service(
+                web::resource("/collections/{name}")
+                .route(web::get().to(on_collection))
+                .route(web::put().to(on_collection_replace))
+                .route(web::delete().to(on_collection_delete))
+                .wrap_fn(require(&[("GET", Scope::Read), ("PUT", Scope::Tag), ("DELETE", Scope::Tag)])))
+            .service(
+                web::resource("/collections/{name}/items")
+                .route(web::post().to(on_collection_add))
+                .route(web::delete().to(on_collection_remove))
+                .wrap_fn(require(&[("POST", Scope::Tag), ("DELETE", Scope::Tag)])))
+            .service(web::resource("/databases").route(web::get().to(on_databases)).wrap_fn(require(READ)))
+            .service(web::resource("/download").route(web::post().to(on_download)).wrap_fn(require(&[("POST", Scope::Download)])))
+            .service(web::resource("/events").route(web::get().to(on_events)).wrap_fn(require(READ)))
+            .service(web::resource("/like").route(web::post().to(on_like)).wrap_fn(require(TAG)))
+            .service(web::resource("/dislike").route(web::post().to(on_dislike)).wrap_fn(require(TAG)))
+            .service(web::resource("/neutral").route(web::post().to(on_neutral)).wrap_fn(require(TAG)))
+            .service(web::resource("/queue").route(web::get().to(on_queue)).wrap_fn(require(READ)))
+            .service(web::resource("/rating").route(web::post().to(on_rating)).wrap_fn(require(TAG)))
+            .service(web::resource("/view").route(web::post().to(on_view)).wrap_fn(require(TAG)))
+            .service(web::resource("/file").route(web::get().to(on_file)).wrap_fn(require(READ)))
+            .service(web::resource("/file/score").route(web::get().to(on_score)).wrap_fn(require(READ)))
+            .service(web::resource("/file/tags").route(web::get().to(on_file_tags)).wrap_fn(require(READ)))
+            .service(
+                web::resource("/history")
+                .route(web::get().to(on_history))
+                .route(web::delete().to(on_history_delete))
+                .wrap_fn(require(&[("GET", Scope::Read), ("DELETE", Scope::Tag)])))
+            .service(web::resource("/history/clear").route(web::post().to(on_history_clear)).wrap_fn(require(TAG)))
+            .service(web::resource("/history/pin").route(web::post().to(on_history_pin)).wrap_fn(require(TAG)))
+            .service(
+                web::resource("/implications")
+                .route(web::get().to(on_implications))
+                .route(web::post().to(on_implication_add))
+                .route(web::delete().to(on_implication_delete))
+                .wrap_fn(require(&[("GET", Scope::Read), ("POST", Scope::Admin), ("DELETE", Scope::Admin)])))
+            .service(web::resource("/roots").route(web::get().to(on_roots)).wrap_fn(require(READ)))
+            .service(
+                web::resource("/saved")
+                .route(web::get().to(on_saved))
+                .route(web::post().to(on_saved_update))
+                .wrap_fn(require(&[("GET", Scope::Read), ("POST", Scope::AliasAdmin)])))
+            .service(
+                web::resource("/saved/{name}")
+                .route(web::get().to(on_saved_search))
+                .route(web::delete().to(on_saved_delete))
+                .wrap_fn(require(&[("GET", Scope::Read), ("DELETE", Scope::AliasAdmin)])))
+            .service(web::resource("/saved/{name}/refresh").route(web::post().to(on_saved_refresh)).wrap_fn(require(&[("POST", Scope::AliasAdmin)])))
+            .service(web::resource("/search").route(web::post().to(on_search)).wrap_fn(require(&[("POST", Scope::Read)])))
+            .service(web::resource("/sources").route(web::get().to(on_sources)).wrap_fn(require(READ)))
+            .service(
+                web::resource("/synonyms")
+                .route(web::get().to(on_synonyms))
+                .route(web::post().to(on_synonym_add))
+                .route(web::delete().to(on_synonym_delete))
+                .wrap_fn(require(&[("GET", Scope::Read), ("POST", Scope::Admin), ("DELETE", Scope::Admin)])))
+            .service(web::resource("/expression/replace_tag").route(web::post().to(on_expression_replace_tag)).wrap_fn(require(&[("POST", Scope::Read)])))
             .service(
                 web::resource("/tags")
                 .route(web::get().to(on_tags))
-                .route(web::post().to(on_set_tags)))
+                .route(web::post().to(on_set_tags))
+                .route(web::put().to(on_replace_tags))
+                .route(web::delete().to(on_remove_tags))
+                .wrap_fn(require(&[("GET", Scope::Read), ("POST", Scope::Tag), ("PUT", Scope::Tag), ("DELETE", Scope::Tag)])))
+            .service(web::resource("/tags/batch").route(web::post().to(on_batch_tags)).wrap_fn(require(TAG)))
+            .service(web::resource("/tags/clear").route(web::post().to(on_clear_tags)).wrap_fn(require(TAG)))
+            .service(web::resource("/tags/complete").route(web::get().to(on_complete_tags)).wrap_fn(require(READ)))
+            .service(web::resource("/tags/cooccurrences").route(web::get().to(on_cooccurrences)).wrap_fn(require(READ)))
+            .service(web::resource("/tags/drop").route(web::post().to(on_drop_tag)).wrap_fn(require(ADMIN)))
+            .service(web::resource("/tags/merge").route(web::post().to(on_merge_tags)).wrap_fn(require(ADMIN)))
+            .service(web::resource("/tags/namespaces").route(web::get().to(on_namespaces)).wrap_fn(require(READ)))
+            .service(web::resource("/tags/rename").route(web::post().to(on_rename_tag)).wrap_fn(require(ADMIN)))
+            .service(web::resource("/tags/stats").route(web::get().to(on_tag_stats)).wrap_fn(require(READ)))
             .service(Files::new("/", &root).index_file("index.html"))
-    }).bind(("0.0.0.0", port))?.run().await
+    });
+
+    for it in &listens {
+        info!("Listen: {}", it);
+        server = match it {
+            Listen::Tcp(address) => server.bind(address)?,
+            #[cfg(unix)]
+            Listen::Unix(path) => {
+                listen::remove_stale_socket(path)?;
+                server.bind_uds(path)?
+            },
+            #[cfg(not(unix))]
+            Listen::Unix(_) =>
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, "Unix domain socket is not supported")),
+        };
+    }
+
+    server.run().await
 }
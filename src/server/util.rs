@@ -1,4 +1,5 @@
 
+use std::path::{Component, Path};
 use std::str;
 
 const MAX_NAME: usize = 255;
@@ -49,3 +50,97 @@ pub fn shorten_path_for(path: &str, n: usize) -> String {
 pub fn shorten_path(path: &str) -> String {
     shorten_path_for(path, MAX_NAME)
 }
+
+pub fn host_of(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, it)| it);
+    if host.is_empty() {
+        return None;
+    }
+    Some(host.to_lowercase())
+}
+
+pub fn filename_from_content_disposition(header: &str) -> Option<String> {
+    let (name, value) = header.split_once(':')?;
+    if !name.trim().eq_ignore_ascii_case("content-disposition") {
+        return None;
+    }
+
+    let mut plain = None;
+    for param in value.split(';').map(str::trim) {
+        if let Some((key, value)) = param.split_once('=') {
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            if key == "filename*" {
+                // RFC 5987: charset'language'percent-encoded
+                let encoded = value.splitn(3, '\'').nth(2)?;
+                return percent_decode(encoded).and_then(sanitize_filename);
+            } else if key == "filename" {
+                plain = sanitize_filename(value.trim_matches('"').to_owned());
+            }
+        }
+    }
+    plain
+}
+
+pub fn filename_from_url(url: &str) -> Option<String> {
+    let path = url.split(|c| c == '?' || c == '#').next()?;
+    let path = path.split_once("://").map_or(path, |(_, it)| it);
+    let (_, name) = path.rsplit_once('/')?;
+    percent_decode(name).and_then(sanitize_filename)
+}
+
+pub fn replace_extension(name: &str, extension: &str) -> String {
+    let (stem, current) = match name.rsplit_once('.') {
+        Some((stem, current)) if !stem.is_empty() && current.len() <= 5 => (stem, Some(current.to_lowercase())),
+        _ => (name, None),
+    };
+    match (current.as_deref(), extension) {
+        (Some(current), _) if current == extension => name.to_owned(),
+        (Some("jpg"), "jpeg") | (Some("jpe"), "jpeg") | (Some("tif"), "tiff") => name.to_owned(),
+        _ => format!("{}.{}", stem, extension),
+    }
+}
+
+/// `cat.png`, `cat (1).png`, `cat (2).png`, ... for `n` = 0, 1, 2, ...
+pub fn numbered_name(name: &str, n: usize) -> String {
+    if n == 0 {
+        return name.to_owned();
+    }
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && extension.len() <= 5 => format!("{} ({}).{}", stem, n, extension),
+        _ => format!("{} ({})", name, n),
+    }
+}
+
+/// Relative path which does not climb up (no root, prefix or `..`)
+pub fn is_inner_path(path: &str) -> bool {
+    Path::new(path).components().all(|it| matches!(it, Component::Normal(_) | Component::CurDir))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut result = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = str::from_utf8(&bytes[i + 1 ..= i + 2]).ok()?;
+            result.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(result).ok()
+}
+
+fn sanitize_filename(name: String) -> Option<String> {
+    let name = name.replace(|c: char| c == '/' || c == '\\' || c.is_control(), "_");
+    let name = name.trim_start_matches('.').trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some(shorten_name_for(name, MAX_NAME))
+}
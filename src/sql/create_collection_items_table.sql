@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS collection_items (
+  collection TEXT,
+  path TEXT,
+  position INTEGER,
+  UNIQUE (collection, path)
+);
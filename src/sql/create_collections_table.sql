@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS collections (
+  name TEXT PRIMARY KEY,
+  created TEXT
+);
@@ -10,5 +10,8 @@ CREATE TABLE IF NOT EXISTS images (
   dhash TEXT,
   created TEXT,
   modified TEXT,
-  accessed TEXT
+  accessed TEXT,
+  rating INTEGER,
+  views INTEGER DEFAULT 0,
+  viewed TEXT
 );
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS implications (
+  child TEXT,
+  parent TEXT,
+  UNIQUE (child, parent)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS roots (
+  name TEXT PRIMARY KEY,
+  path TEXT UNIQUE,
+  created TEXT
+);
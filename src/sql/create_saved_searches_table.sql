@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS saved_searches (
+  name TEXT PRIMARY KEY,
+  expression TEXT,
+  description TEXT,
+  sort TEXT,
+  max_results INTEGER,
+  count INTEGER,
+  counted TEXT
+);
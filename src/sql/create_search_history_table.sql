@@ -2,5 +2,7 @@ CREATE TABLE IF NOT EXISTS search_history (
   expression TEXT PRIMARY KEY,
   uses INTEGER,
   created TEXT,
-  modified TEXT
+  modified TEXT,
+  pinned INTEGER DEFAULT 0,
+  results INTEGER
 );
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS source_priorities (
+  source TEXT PRIMARY KEY,
+  priority INTEGER
+);
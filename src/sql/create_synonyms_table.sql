@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS synonyms (
+  alias TEXT PRIMARY KEY,
+  canonical TEXT
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS tag_removals (
+  tag TEXT,
+  path TEXT,
+  source TEXT,
+  removed TEXT,
+  UNIQUE (tag, path, source)
+);
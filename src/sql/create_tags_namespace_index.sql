@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS tags_index_namespace ON tags(namespace);
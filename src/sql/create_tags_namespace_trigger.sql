@@ -0,0 +1,5 @@
+CREATE TRIGGER IF NOT EXISTS tags_namespace AFTER INSERT ON tags
+WHEN instr(NEW.tag, ':') > 1 AND instr(NEW.tag, ':') < length(NEW.tag)
+BEGIN
+  UPDATE tags SET namespace = substr(NEW.tag, 1, instr(NEW.tag, ':') - 1) WHERE rowid = NEW.rowid;
+END
@@ -2,5 +2,7 @@ CREATE TABLE IF NOT EXISTS tags (
   tag TEXT KEY,
   path TEXT,
   source TEXT,
+  namespace TEXT,
+  added TEXT,
   UNIQUE (tag, path, source)
 );
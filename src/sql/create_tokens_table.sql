@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS tokens (
+  name TEXT PRIMARY KEY,
+  hash TEXT UNIQUE,
+  scopes TEXT,
+  created TEXT
+);
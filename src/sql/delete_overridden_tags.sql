@@ -0,0 +1,3 @@
+DELETE FROM tags
+WHERE tag = ?1 AND path = ?2
+  AND COALESCE((SELECT priority FROM source_priorities WHERE source = tags.source), 0) < ?3
@@ -0,0 +1,3 @@
+DELETE FROM tag_removals
+WHERE tag = ?1 AND path = ?2
+  AND COALESCE((SELECT priority FROM source_priorities WHERE source = tag_removals.source), 0) <= ?3
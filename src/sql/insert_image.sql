@@ -1,3 +1,3 @@
-INSERT INTO images
+INSERT INTO images (path, width, height, ratio_width, ratio_height, format, animation, file_size, dhash, created, modified, accessed)
 SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12
 WHERE (SELECT changes() = 0)
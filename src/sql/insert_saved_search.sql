@@ -0,0 +1,3 @@
+INSERT INTO saved_searches (name, expression, description, sort, max_results)
+SELECT ?1, ?2, ?3, ?4, ?5
+WHERE (SELECT changes() = 0)
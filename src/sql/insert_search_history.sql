@@ -1,5 +1,5 @@
-INSERT INTO search_history
-SELECT ?1, 1, ?2, ?2
+INSERT INTO search_history (expression, uses, created, modified, results)
+SELECT ?1, 1, ?2, ?2, ?3
 WHERE NOT EXISTS (
   SELECT 1 FROM search_history
   WHERE expression = ?1
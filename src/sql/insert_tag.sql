@@ -1,6 +1,10 @@
-INSERT INTO tags
-SELECT ?1, ?2, ?3
+INSERT INTO tags (tag, path, source, added)
+SELECT ?1, ?2, ?3, ?5
 WHERE NOT EXISTS (
   SELECT 1 FROM tags
   WHERE tag = ?1 AND path = ?2 AND source = ?3
+) AND NOT EXISTS (
+  SELECT 1 FROM tag_removals AS removal
+  WHERE removal.tag = ?1 AND removal.path = ?2
+    AND COALESCE((SELECT priority FROM source_priorities WHERE source = removal.source), 0) > ?4
 )
@@ -0,0 +1,2 @@
+INSERT OR REPLACE INTO tag_removals (tag, path, source, removed)
+VALUES (?1, ?2, ?3, ?4)
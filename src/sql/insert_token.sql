@@ -0,0 +1 @@
+INSERT INTO tokens (name, hash, scopes, created) VALUES (?1, ?2, ?3, ?4)
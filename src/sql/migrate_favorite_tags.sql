@@ -0,0 +1,9 @@
+UPDATE images
+SET rating = (
+  SELECT favorite_rating(tag)
+  FROM tags
+  WHERE tags.path = images.path AND source = 'noir' AND favorite_rating(tag) IS NOT NULL
+  ORDER BY favorite_rating(tag) DESC
+  LIMIT 1
+)
+WHERE path IN (SELECT path FROM tags WHERE source = 'noir' AND favorite_rating(tag) IS NOT NULL)
@@ -0,0 +1,3 @@
+INSERT OR IGNORE INTO tags (tag, path, source, added)
+SELECT ?2, path, source, added FROM tags
+WHERE tag = ?1 AND (?3 IS NULL OR source = ?3)
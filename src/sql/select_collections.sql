@@ -0,0 +1,5 @@
+SELECT collections.name, COUNT(collection_items.path), collections.created
+FROM collections
+LEFT JOIN collection_items ON collection_items.collection = collections.name
+GROUP BY collections.name
+ORDER BY collections.name
@@ -0,0 +1,11 @@
+SELECT roots.name, roots.path, COUNT(images.path), COALESCE(SUM(images.file_size), 0), roots.created
+FROM roots
+LEFT JOIN images
+  ON rtrim(roots.path, '/') || '/' < images.path AND images.path < rtrim(roots.path, '/') || '0'
+  AND NOT EXISTS (
+    SELECT 1 FROM roots AS nested
+    WHERE length(rtrim(roots.path, '/')) < length(rtrim(nested.path, '/'))
+      AND substr(images.path, 1, length(rtrim(nested.path, '/')) + 1) = rtrim(nested.path, '/') || '/'
+  )
+GROUP BY roots.name
+ORDER BY roots.name
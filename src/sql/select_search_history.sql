@@ -0,0 +1,6 @@
+SELECT expression, uses, pinned, results, modified
+FROM search_history
+WHERE (?1 IS NULL OR instr(expression, ?1) > 0)
+  AND (?2 IS NULL OR pinned = ?2)
+ORDER BY pinned DESC, modified DESC
+LIMIT IFNULL(?3, -1)
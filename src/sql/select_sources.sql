@@ -0,0 +1,9 @@
+SELECT known.source, COALESCE(source_priorities.priority, 0), COALESCE(counts.rows, 0)
+FROM (
+  SELECT source FROM tags
+  UNION SELECT source FROM tag_removals
+  UNION SELECT source FROM source_priorities
+) AS known
+LEFT JOIN source_priorities ON source_priorities.source = known.source
+LEFT JOIN (SELECT source, COUNT(*) AS rows FROM tags GROUP BY source) AS counts ON counts.source = known.source
+ORDER BY 2 DESC, known.source
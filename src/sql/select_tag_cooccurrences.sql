@@ -0,0 +1,7 @@
+SELECT other.tag, COUNT(DISTINCT other.path)
+FROM tags AS target
+JOIN tags AS other ON other.path = target.path AND other.tag != target.tag
+WHERE target.tag = ?1
+GROUP BY other.tag
+ORDER BY 2 DESC, other.tag
+LIMIT ?2
@@ -0,0 +1,7 @@
+DELETE FROM search_history
+WHERE pinned = 0 AND expression NOT IN (
+  SELECT expression FROM search_history
+  WHERE pinned = 0
+  ORDER BY modified DESC
+  LIMIT ?1
+)
@@ -0,0 +1,8 @@
+UPDATE saved_searches
+SET expression = ?2,
+    description = ?3,
+    sort = ?4,
+    max_results = ?5,
+    count = NULL,
+    counted = NULL
+WHERE name = ?1
@@ -1,4 +1,3 @@
 UPDATE search_history
-SET uses = uses + 1, modified = ?2
+SET uses = uses + 1, modified = ?2, results = IFNULL(?3, results)
 WHERE expression = ?1
-
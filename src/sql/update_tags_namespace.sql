@@ -0,0 +1,2 @@
+UPDATE tags SET namespace = substr(tag, 1, instr(tag, ':') - 1)
+WHERE instr(tag, ':') > 1 AND instr(tag, ':') < length(tag)
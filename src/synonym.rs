@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use serde_derive::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+
+
+/// `alias` is written as `canonical` (e.g. `grey` -> `gray`)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Synonym {
+    pub alias: String,
+    pub canonical: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Normalizer {
+    pub case_folding: bool,
+    /// Unicode NFKC
+    pub unicode: bool,
+    synonyms: HashMap<String, String>,
+}
+
+
+impl Normalizer {
+    pub fn new(case_folding: bool, unicode: bool) -> Self {
+        Normalizer { case_folding, unicode, synonyms: HashMap::new() }
+    }
+
+    pub fn add_synonym(&mut self, alias: &str, canonical: &str) {
+        let alias = self.fold(alias);
+        let canonical = self.fold(canonical);
+        self.synonyms.insert(alias, canonical);
+    }
+
+    /// Canonical form of the tag, following the chain of the synonyms (e.g. `grey` -> `gray` -> `ash`)
+    pub fn normalize(&self, tag: &str) -> String {
+        let mut tag = self.fold(tag);
+        for _ in 0 .. self.synonyms.len() {
+            match self.synonyms.get(&tag) {
+                Some(canonical) => tag = canonical.clone(),
+                None => break,
+            }
+        }
+        tag
+    }
+
+    /// Whether `alias` -> `canonical` makes a cycle (including `alias` == `canonical`)
+    pub fn would_cycle(&self, alias: &str, canonical: &str) -> bool {
+        let alias = self.fold(alias);
+        let mut tag = self.fold(canonical);
+        for _ in 0 ..= self.synonyms.len() {
+            if tag == alias {
+                return true;
+            }
+            match self.synonyms.get(&tag) {
+                Some(next) => tag = next.clone(),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn fold(&self, tag: &str) -> String {
+        let tag: String = if self.unicode { tag.nfkc().collect() } else { tag.to_owned() };
+        if self.case_folding { tag.to_lowercase() } else { tag }
+    }
+}
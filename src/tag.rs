@@ -1,4 +1,5 @@
 
+use std::collections::{BTreeMap, HashSet};
 use std::convert::From;
 use std::str::FromStr;
 
@@ -6,17 +7,78 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use rusqlite::types::{ToSql, ToSqlOutput, Value, ValueRef};
 use rusqlite::{Result as QResult};
+use serde_derive::{Deserialize, Serialize};
 
 use crate::errors::{AppError, AppResult};
 
 
 
 lazy_static! {
-    static ref TAG_NAME: Regex = Regex::new(r"\S+").unwrap();
+    static ref TAG_NAME: Regex = Regex::new(r"^\S+$").unwrap();
 }
 
 pub struct Tag(String);
 
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagOperation {
+    Add,
+    Remove,
+    Set,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct BatchResult {
+    /// Number of changed paths
+    pub paths: usize,
+    /// Number of changed rows
+    pub rows: usize,
+}
+
+/// `#tag` in expressions
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagQuery<'a> {
+    /// `#rating:>=3`
+    Compare { namespace: &'a str, operator: &'a str, value: f64 },
+    Exact(&'a str),
+    /// `#artist:*`
+    Namespace(&'a str),
+    /// `#artist:foo*`
+    Pattern { namespace: &'a str, pattern: &'a str },
+}
+
+/// `@source` or `@!source` of `#tag` in expressions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SourceFilter<'a> {
+    pub negated: bool,
+    pub source: &'a str,
+}
+
+/// Tag source and its priority. Removals by a source override additions by lower priority sources.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Source {
+    pub name: String,
+    pub priority: i64,
+    /// Number of tag rows
+    pub rows: u64,
+}
+
+/// Tags of a path grouped by namespace
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct GroupedTags {
+    /// Values for each namespace
+    pub namespaces: BTreeMap<String, Vec<String>>,
+    /// Tags without namespace
+    pub tags: Vec<String>,
+}
+
+/// Rows to change for a path and a source
+#[derive(Debug, Default, PartialEq)]
+pub struct TagDiff {
+    pub delete: Vec<String>,
+    pub insert: Vec<String>,
+}
+
 
 
 impl From<Tag> for Value {
@@ -31,6 +93,12 @@ impl ToSql for Tag {
     }
 }
 
+impl AsRef<str> for Tag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
 impl FromStr for Tag {
     type Err = AppError;
     fn from_str(tag: &str) -> AppResult<Self> {
@@ -42,3 +110,118 @@ impl FromStr for Tag {
         }
     }
 }
+
+impl<'a> FromIterator<&'a str> for GroupedTags {
+    fn from_iter<T: IntoIterator<Item = &'a str>>(iter: T) -> Self {
+        let mut result = GroupedTags::default();
+        for tag in iter {
+            match split_namespace(tag) {
+                (Some(namespace), value) => result.namespaces.entry(namespace.to_owned()).or_default().push(value.to_owned()),
+                (None, tag) => result.tags.push(tag.to_owned()),
+            }
+        }
+        result
+    }
+}
+
+/// Finite number of the tag value (e.g. `3` of `rating:3`)
+pub fn tag_number(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|it| it.is_finite())
+}
+
+impl<'a> TagQuery<'a> {
+    pub fn parse(tag: &'a str) -> AppResult<Self> {
+        let (namespace, value) = match split_namespace(tag) {
+            (Some(namespace), value) => (namespace, value),
+            (None, _) => return Ok(TagQuery::Exact(tag)),
+        };
+
+        if value == "*" {
+            return Ok(TagQuery::Namespace(namespace));
+        }
+
+        for operator in &[">=", "<=", "!=", ">", "<", "="] {
+            if let Some(number) = value.strip_prefix(operator) {
+                if let Ok(number) = number.parse::<f64>() {
+                    if !number.is_finite() {
+                        return Err(AppError::InvalidTagFormat(tag.to_owned()));
+                    }
+                    return Ok(TagQuery::Compare { namespace, operator: *operator, value: number });
+                }
+            }
+        }
+
+        if value.contains(|c| c == '*' || c == '?') {
+            return Ok(TagQuery::Pattern { namespace, pattern: tag });
+        }
+
+        Ok(TagQuery::Exact(tag))
+    }
+}
+
+impl TagOperation {
+    pub fn diff(self, existing: &[String], tags: &[Tag]) -> TagDiff {
+        use TagOperation::*;
+
+        let requested = |it: &String| tags.iter().any(|tag| &tag.0 == it);
+
+        let mut insert: Vec<String> = vec![];
+        if self != Remove {
+            for tag in tags {
+                if !existing.contains(&tag.0) && !insert.contains(&tag.0) {
+                    insert.push(tag.0.clone());
+                }
+            }
+        }
+
+        let delete = match self {
+            Add => vec![],
+            Remove => existing.iter().filter(|it| requested(it)).cloned().collect(),
+            Set => existing.iter().filter(|it| !requested(it)).cloned().collect(),
+        };
+
+        TagDiff { delete, insert }
+    }
+}
+
+impl FromStr for TagOperation {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        use TagOperation::*;
+
+        let result = match s {
+            "add" => Add,
+            "remove" => Remove,
+            "set" => Set,
+            _ => return Err(AppError::InvalidTagOperation(s.to_owned())),
+        };
+        Ok(result)
+    }
+}
+
+
+/// `artist:foo` => (Some("artist"), "foo")
+pub fn split_namespace(tag: &str) -> (Option<&str>, &str) {
+    match tag.split_once(':') {
+        Some((namespace, value)) if !namespace.is_empty() && !value.is_empty() => (Some(namespace), value),
+        _ => (None, tag),
+    }
+}
+
+/// `cat@manual` => ("cat", Some(manual)), `cat@!auto` => ("cat", Some(not auto)).
+/// The suffix is split only if it is one of `sources` (e.g. `user@example` is a tag).
+pub fn split_source<'a>(tag: &'a str, sources: &HashSet<String>) -> (&'a str, Option<SourceFilter<'a>>) {
+    let (name, source) = match tag.rsplit_once('@') {
+        Some((name, source)) if !name.is_empty() => (name, source),
+        _ => return (tag, None),
+    };
+    let (negated, source) = match source.strip_prefix('!') {
+        Some(source) => (true, source),
+        None => (false, source),
+    };
+    if !sources.contains(source) {
+        return (tag, None);
+    }
+    (name, Some(SourceFilter { negated, source }))
+}
@@ -0,0 +1,60 @@
+
+use std::collections::BTreeMap;
+
+use serde_derive::Serialize;
+
+
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    /// Number of paths
+    pub count: u64,
+    /// Number of paths for each source
+    pub sources: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Cooccurrence {
+    pub tag: String,
+    /// Number of paths which have the both tags
+    pub count: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NamespaceCount {
+    pub namespace: String,
+    /// Number of distinct tags
+    pub tags: u64,
+}
+
+
+/// Ranks tags by prefix, substring and subsequence matching, then by usage
+pub fn complete(counts: &[TagCount], query: &str, limit: usize) -> Vec<TagCount> {
+    let query = query.to_lowercase();
+    let mut found: Vec<(usize, &TagCount)> = counts
+        .iter()
+        .filter_map(|it| match_rank(&it.tag.to_lowercase(), &query).map(|rank| (rank, it)))
+        .collect();
+    found.sort_by(|(ra, a), (rb, b)| {
+        ra.cmp(rb)
+            .then(b.count.cmp(&a.count))
+            .then(a.tag.len().cmp(&b.tag.len()))
+            .then(a.tag.cmp(&b.tag))
+    });
+    found.into_iter().take(limit).map(|(_, it)| it.clone()).collect()
+}
+
+fn match_rank(tag: &str, query: &str) -> Option<usize> {
+    if tag.starts_with(query) {
+        return Some(0);
+    }
+    if tag.contains(query) {
+        return Some(1);
+    }
+    let mut chars = tag.chars();
+    if query.chars().all(|q| chars.any(|c| c == q)) {
+        return Some(2);
+    }
+    None
+}
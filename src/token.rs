@@ -0,0 +1,93 @@
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::offset::Utc;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppResult};
+
+
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Token {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    /// All of the scopes
+    Admin,
+    AliasAdmin,
+    Download,
+    Read,
+    Tag,
+}
+
+
+impl Scope {
+    pub fn allows(self, required: Scope) -> bool {
+        self == Scope::Admin || self == required
+    }
+
+    pub fn as_str(self) -> &'static str {
+        use Scope::*;
+
+        match self {
+            Admin => "admin",
+            AliasAdmin => "alias-admin",
+            Download => "download",
+            Read => "read",
+            Tag => "tag",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        use Scope::*;
+
+        let result = match s {
+            "admin" => Admin,
+            "alias-admin" => AliasAdmin,
+            "download" => Download,
+            "read" => Read,
+            "tag" => Tag,
+            _ => return Err(AppError::InvalidScope(s.to_owned())),
+        };
+        Ok(result)
+    }
+}
+
+pub fn generate_secret() -> AppResult<String> {
+    let mut bytes = [0u8; 24];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|it| format!("{:02x}", it)).collect())
+}
+
+/// Hex SHA-1 of the secret. Only this is stored in the database.
+pub fn hash_secret(secret: &str) -> String {
+    sha1::Sha1::from(secret).digest().to_string()
+}
+
+pub fn join_scopes(scopes: &[Scope]) -> String {
+    scopes.iter().map(|it| it.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+pub fn split_scopes(scopes: &str) -> AppResult<Vec<Scope>> {
+    scopes.split_whitespace().map(Scope::from_str).collect()
+}
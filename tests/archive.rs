@@ -0,0 +1,113 @@
+use std::fs;
+use std::str::FromStr;
+
+use noir::archive::{ConflictPolicy, PrefixRewrite, Record, export, import};
+use noir::search_history::HistoryFilter;
+use noir::tag::Tag;
+
+mod common;
+use common::{meta, open};
+
+
+fn tags(tags: &[&str]) -> Vec<Tag> {
+    tags.iter().map(|it| Tag::from_str(it).unwrap()).collect()
+}
+
+fn pair(tag: &str, source: &str) -> (String, String) {
+    (tag.to_owned(), source.to_owned())
+}
+
+
+
+#[test]
+fn test_conflict_policy() {
+    assert_eq!("skip".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::Skip);
+    assert_eq!("overwrite".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::Overwrite);
+    assert_eq!("union".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::Union);
+    assert!("merge".parse::<ConflictPolicy>().is_err());
+}
+
+#[test]
+fn test_prefix_rewrite() {
+    let rewrite: PrefixRewrite = "/home/alice/pics:/mnt/pics".parse().unwrap();
+    assert_eq!(rewrite.apply("/home/alice/pics/cat.png"), "/mnt/pics/cat.png");
+    assert_eq!(rewrite.apply("/home/bob/cat.png"), "/home/bob/cat.png");
+    assert_eq!(rewrite.apply("/home/alice/pictures/cat.png"), "/home/alice/pictures/cat.png");
+    assert_eq!(rewrite.apply("/home/alice/pics"), "/mnt/pics");
+
+    let rewrite: PrefixRewrite = "/home/alice/pics/:/mnt/pics/".parse().unwrap();
+    assert_eq!(rewrite.apply("/home/alice/pics/cat.png"), "/mnt/pics/cat.png");
+
+    assert!("/no/separator".parse::<PrefixRewrite>().is_err());
+    assert!(":/mnt/pics".parse::<PrefixRewrite>().is_err());
+}
+
+#[test]
+fn test_record() {
+    let line = r##"{"type":"alias","name":"cat","expression":"#cat","recursive":false}"##;
+    match serde_json::from_str::<Record>(line).unwrap() {
+        Record::Alias { name, expression, recursive } => {
+            assert_eq!(name, "cat");
+            assert_eq!(expression, "#cat");
+            assert!(!recursive);
+        },
+        it => panic!("Unexpected: {:?}", it),
+    }
+
+    let line = r#"{"type":"collection","name":"deck","created":null,"paths":["/a.png"]}"#;
+    let record: Record = serde_json::from_str(line).unwrap();
+    assert_eq!(serde_json::to_string(&record).unwrap(), line);
+}
+
+#[test]
+fn test_export_and_import() {
+    let (from_dir, from) = open("archive-export");
+    from.upsert(&meta("/pics/a.png", 1)).unwrap();
+    from.add_tags("/pics/a.png", &tags(&["cat"]), "manual").unwrap();
+    from.add_tags("/pics/a.png", &tags(&["dog"]), "crawler").unwrap();
+    from.set_rating("/pics/a.png", Some(4)).unwrap();
+    from.add_view("/pics/a.png").unwrap();
+    from.upsert_alias("pets", "#cat OR #dog", false).unwrap();
+    from.add_search_history("#cat", Some(1)).unwrap();
+    from.create_collection("fav").unwrap();
+    from.set_collection_paths("fav", &["/pics/a.png".to_owned()]).unwrap();
+
+    let mut out = vec![];
+    export(&from, &mut out).unwrap();
+
+    let (to_dir, to) = open("archive-import");
+    let rewrite: PrefixRewrite = "/pics:/mnt".parse().unwrap();
+    let result = import(&to, &out[..], ConflictPolicy::Skip, Some(&rewrite)).unwrap();
+    assert_eq!((result.images, result.aliases, result.history, result.collections, result.skipped), (1, 1, 1, 1, 0));
+
+    assert!(!to.path_exists("/pics/a.png").unwrap());
+    assert_eq!(to.sourced_tags_by_path("/mnt/a.png").unwrap(), vec![pair("dog", "crawler"), pair("cat", "manual")]);
+    let score = to.score("/mnt/a.png").unwrap();
+    assert_eq!((score.rating, score.views), (Some(4), 1));
+    assert_eq!(to.aliases().unwrap()["pets"].expression, "#cat OR #dog");
+    let history = to.search_history(&HistoryFilter::default()).unwrap();
+    assert_eq!(history.iter().map(|it| (it.expression.as_str(), it.results)).collect::<Vec<_>>(), vec![("#cat", Some(1))]);
+    assert_eq!(to.collection_paths("fav").unwrap(), vec!["/mnt/a.png".to_owned()]);
+
+    // Union keeps the existing tags and adds the imported ones
+    to.set_tags("/mnt/a.png", &tags(&["bird"]), "manual").unwrap();
+    let result = import(&to, &out[..], ConflictPolicy::Union, Some(&rewrite)).unwrap();
+    assert_eq!(result.images, 1);
+    assert_eq!(
+        to.sourced_tags_by_path("/mnt/a.png").unwrap(),
+        vec![pair("dog", "crawler"), pair("bird", "manual"), pair("cat", "manual")]);
+
+    // Skip keeps them as they are
+    to.set_tags("/mnt/a.png", &tags(&["bird"]), "manual").unwrap();
+    let result = import(&to, &out[..], ConflictPolicy::Skip, Some(&rewrite)).unwrap();
+    assert_eq!((result.images, result.aliases, result.history, result.collections, result.skipped), (0, 0, 0, 0, 4));
+    assert_eq!(to.sourced_tags_by_path("/mnt/a.png").unwrap(), vec![pair("dog", "crawler"), pair("bird", "manual")]);
+
+    // Overwrite replaces the tags per source
+    let result = import(&to, &out[..], ConflictPolicy::Overwrite, Some(&rewrite)).unwrap();
+    assert_eq!(result.images, 1);
+    assert_eq!(to.sourced_tags_by_path("/mnt/a.png").unwrap(), vec![pair("dog", "crawler"), pair("cat", "manual")]);
+
+    let _ = fs::remove_dir_all(&from_dir);
+    let _ = fs::remove_dir_all(&to_dir);
+}
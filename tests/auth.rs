@@ -0,0 +1,35 @@
+
+use actix_web::http::Method;
+
+use noir::server::auth::{ADMIN, READ, TAG, required_scope as rs};
+use noir::token::{Scope, hash_secret};
+
+
+#[test]
+fn test_required_scope() {
+    let tags: &[(&str, Scope)] = &[("GET", Scope::Read), ("POST", Scope::Tag), ("DELETE", Scope::Tag)];
+    assert_eq!(rs(tags, &Method::GET), Scope::Read);
+    assert_eq!(rs(tags, &Method::POST), Scope::Tag);
+    assert_eq!(rs(tags, &Method::DELETE), Scope::Tag);
+    assert_eq!(rs(tags, &Method::PUT), Scope::Admin);
+    assert_eq!(rs(tags, &Method::HEAD), Scope::Admin);
+
+    assert_eq!(rs(READ, &Method::GET), Scope::Read);
+    assert_eq!(rs(READ, &Method::POST), Scope::Admin);
+    assert_eq!(rs(TAG, &Method::POST), Scope::Tag);
+    assert_eq!(rs(ADMIN, &Method::POST), Scope::Admin);
+    assert_eq!(rs(&[], &Method::GET), Scope::Admin);
+}
+
+#[test]
+fn test_hash_secret() {
+    assert_eq!(hash_secret("abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_ne!(hash_secret("abc"), hash_secret("abd"));
+}
+
+#[test]
+fn test_allows() {
+    assert!(Scope::Admin.allows(Scope::Tag));
+    assert!(Scope::Tag.allows(Scope::Tag));
+    assert!(!Scope::Read.allows(Scope::Tag));
+}
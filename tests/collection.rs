@@ -0,0 +1,87 @@
+use std::fs;
+
+use noir::collection::{insert, move_item, remove};
+use noir::errors::AppError;
+
+mod common;
+use common::{meta, open};
+
+
+
+fn v(items: &[&str]) -> Vec<String> {
+    items.iter().map(|it| it.to_string()).collect()
+}
+
+
+#[test]
+fn test_insert() {
+    let mut items = v(&["a", "b", "c"]);
+    insert(&mut items, &v(&["d"]), None);
+    assert_eq!(items, v(&["a", "b", "c", "d"]));
+
+    insert(&mut items, &v(&["e", "f"]), Some(1));
+    assert_eq!(items, v(&["a", "e", "f", "b", "c", "d"]));
+
+    insert(&mut items, &v(&["d", "a"]), Some(2));
+    assert_eq!(items, v(&["e", "d", "a", "f", "b", "c"]));
+
+    insert(&mut items, &v(&["x", "x"]), Some(100));
+    assert_eq!(items, v(&["e", "d", "a", "f", "b", "c", "x"]));
+}
+
+#[test]
+fn test_move_item() {
+    let mut items = v(&["a", "b", "c"]);
+    assert!(move_item(&mut items, "a", 2));
+    assert_eq!(items, v(&["b", "c", "a"]));
+    assert!(move_item(&mut items, "a", 0));
+    assert_eq!(items, v(&["a", "b", "c"]));
+    assert!(!move_item(&mut items, "z", 0));
+}
+
+#[test]
+fn test_remove() {
+    let mut items = v(&["a", "b", "c"]);
+    assert_eq!(remove(&mut items, &v(&["b", "z"])), 1);
+    assert_eq!(items, v(&["a", "c"]));
+}
+
+#[test]
+fn test_database_collection() {
+    let (dir, db) = open("collection");
+    db.upsert(&meta("/a.png", 1)).unwrap();
+    db.upsert(&meta("/b.png", 1)).unwrap();
+
+    db.create_collection("fav").unwrap();
+    assert!(db.create_collection("fav").is_err());
+    assert_eq!(db.collection_paths("fav").unwrap(), v(&[]));
+
+    db.set_collection_paths("fav", &v(&["/b.png", "/a.png"])).unwrap();
+    assert_eq!(db.collection_paths("fav").unwrap(), v(&["/b.png", "/a.png"]));
+    let mut selected = vec![];
+    db.select_collection("fav", |meta| {
+        selected.push(meta.file.path.clone());
+        Ok(())
+    }).unwrap();
+    assert_eq!(selected, v(&["/b.png", "/a.png"]));
+
+    let collections = db.collections().unwrap();
+    assert_eq!(collections.iter().map(|it| (it.name.as_str(), it.items)).collect::<Vec<_>>(), vec![("fav", 2)]);
+
+    match db.set_collection_paths("fav", &v(&["/a.png", "/missing.png"])) {
+        Err(AppError::PathNotFound(it)) => assert_eq!(it, "/missing.png"),
+        it => panic!("Unexpected: {:?}", it),
+    }
+    assert_eq!(db.collection_paths("fav").unwrap(), v(&["/b.png", "/a.png"]));
+
+    match db.collection_paths("nothing") {
+        Err(AppError::CollectionNotFound(it)) => assert_eq!(it, "nothing"),
+        it => panic!("Unexpected: {:?}", it),
+    }
+
+    assert!(db.delete_collection("fav").unwrap());
+    assert!(!db.delete_collection("fav").unwrap());
+    assert!(db.collections().unwrap().is_empty());
+
+    let _ = fs::remove_dir_all(&dir);
+}
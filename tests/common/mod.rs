@@ -0,0 +1,36 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use noir::database::Database;
+use noir::meta::{Dimensions, FileMeta, Meta};
+
+
+
+/// Empty directory for the test `name`
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("noir-test-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// New database in `temp_dir(name)`
+pub fn open(name: &str) -> (PathBuf, Database) {
+    let dir = temp_dir(name);
+    let db = Database::open(&dir.join("db.sqlite")).unwrap();
+    (dir, db)
+}
+
+pub fn meta(path: &str, size: u32) -> Meta {
+    Meta {
+        animation: false,
+        dhash: None,
+        dimensions: Dimensions { width: 10, height: 20 },
+        file: FileMeta { path: path.to_owned(), size, created: None, modified: None, accessed: None },
+        format: "png",
+    }
+}
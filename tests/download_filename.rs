@@ -0,0 +1,56 @@
+
+use noir::server::util::{
+    filename_from_content_disposition as fcd,
+    filename_from_url as fu,
+    is_inner_path,
+    numbered_name,
+    replace_extension as re,
+};
+
+
+#[test]
+fn test_filename_from_content_disposition() {
+    assert_eq!(fcd("Content-Disposition: attachment; filename=\"cat.jpg\"\r\n"), Some("cat.jpg".to_owned()));
+    assert_eq!(fcd("content-disposition: inline; filename=cat.png"), Some("cat.png".to_owned()));
+    assert_eq!(fcd("Content-Disposition: attachment; filename=\"a.jpg\"; filename*=UTF-8''%E3%81%82.jpg"), Some("あ.jpg".to_owned()));
+    assert_eq!(fcd("Content-Disposition: attachment; filename=\"../../etc/passwd\""), Some("_.._etc_passwd".to_owned()));
+    assert_eq!(fcd("Content-Disposition: attachment"), None);
+    assert_eq!(fcd("Content-Type: image/png"), None);
+}
+
+#[test]
+fn test_filename_from_url() {
+    assert_eq!(fu("https://example.com/a/cat.jpg"), Some("cat.jpg".to_owned()));
+    assert_eq!(fu("https://example.com/a/cat.jpg?size=large#top"), Some("cat.jpg".to_owned()));
+    assert_eq!(fu("https://example.com/%E3%81%82.png"), Some("あ.png".to_owned()));
+    assert_eq!(fu("https://example.com/"), None);
+    assert_eq!(fu("https://example.com"), None);
+}
+
+#[test]
+fn test_replace_extension() {
+    assert_eq!(re("cat.png", "png"), "cat.png".to_owned());
+    assert_eq!(re("cat.PNG", "png"), "cat.PNG".to_owned());
+    assert_eq!(re("cat.jpg", "jpeg"), "cat.jpg".to_owned());
+    assert_eq!(re("cat.png", "webp"), "cat.webp".to_owned());
+    assert_eq!(re("cat", "gif"), "cat.gif".to_owned());
+    assert_eq!(re("cat.php", "jpeg"), "cat.jpeg".to_owned());
+}
+
+#[test]
+fn test_numbered_name() {
+    assert_eq!(numbered_name("cat.png", 0), "cat.png".to_owned());
+    assert_eq!(numbered_name("cat.png", 1), "cat (1).png".to_owned());
+    assert_eq!(numbered_name("cat", 2), "cat (2)".to_owned());
+    assert_eq!(numbered_name(".hidden", 1), ".hidden (1)".to_owned());
+}
+
+#[test]
+fn test_is_inner_path() {
+    assert!(is_inner_path(""));
+    assert!(is_inner_path("cats/"));
+    assert!(is_inner_path("./cats/cat.png"));
+    assert!(!is_inner_path("/etc/passwd"));
+    assert!(!is_inner_path("../cats"));
+    assert!(!is_inner_path("cats/../../etc"));
+}
@@ -0,0 +1,38 @@
+
+use actix_web::ResponseError;
+use actix_web::http::StatusCode;
+
+use noir::errors::AppError;
+
+
+#[test]
+fn test_status_code() {
+    assert_eq!(AppError::Void.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(AppError::PathNotFound("/a.png".to_owned()).status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(AppError::InvalidTagFormat("".to_owned()).status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(AppError::Standard("error").status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(AppError::UnknownUtf8.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let not_found = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+    assert_eq!(AppError::Io(not_found).status_code(), StatusCode::NOT_FOUND);
+    let denied = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+    assert_eq!(AppError::Io(denied).status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let wrapped = AppError::WithPath(Box::new(AppError::Void), "/a.png".into());
+    assert_eq!(wrapped.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(wrapped.code(), "not_found");
+}
+
+#[test]
+fn test_in_expression() {
+    let error = AppError::InvalidQuery(r#"near "FROM": syntax error"#.to_owned(), None);
+    let error = error.in_expression("#cat and FROM");
+    assert_eq!(error.position(), Some(9));
+    assert_eq!(error.code(), "invalid_query");
+
+    let error = AppError::InvalidQuery(r#"near "FROM": syntax error"#.to_owned(), None);
+    assert_eq!(error.in_expression("#FROM and  FROM").position(), Some(11));
+
+    let error = AppError::InvalidQuery("no such column: foo".to_owned(), None);
+    assert_eq!(error.in_expression("foo = 1").position(), None);
+}
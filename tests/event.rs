@@ -0,0 +1,49 @@
+
+use noir::event::{Event, EventBus, SUBSCRIBER_BUFFER};
+
+
+#[test]
+fn test_publish() {
+    let bus = EventBus::default();
+    let mut rx = bus.subscribe();
+
+    bus.publish(Event::ImageAdded { path: "/a.png".to_owned() });
+
+    let event = rx.try_next().unwrap().unwrap();
+    assert_eq!(
+        serde_json::to_string(&event).unwrap(),
+        r#"{"type":"image-added","path":"/a.png"}"#);
+    assert!(rx.try_next().is_err());
+}
+
+#[test]
+fn test_closed_subscriber() {
+    let bus = EventBus::default();
+    let rx = bus.subscribe();
+    drop(rx);
+
+    bus.publish(Event::AliasChanged { name: "cat".to_owned() });
+
+    let mut rx = bus.subscribe();
+    bus.publish(Event::AliasChanged { name: "dog".to_owned() });
+    assert!(rx.try_next().unwrap().is_some());
+}
+
+#[test]
+fn test_lagging_subscriber() {
+    let bus = EventBus::default();
+    let mut rx = bus.subscribe();
+
+    for _ in 0 ..= SUBSCRIBER_BUFFER + 1 {
+        bus.publish(Event::AliasChanged { name: "cat".to_owned() });
+    }
+
+    let mut received = 0;
+    while let Ok(Some(_)) = rx.try_next() {
+        received += 1;
+    }
+    // The buffer and the slot of the sender
+    assert_eq!(received, SUBSCRIBER_BUFFER + 1);
+    // Disconnected
+    assert!(matches!(rx.try_next(), Ok(None)));
+}
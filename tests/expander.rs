@@ -1,9 +1,12 @@
 
-use maplit::hashmap;
+use maplit::{hashmap, hashset};
 
 use noir::alias::Alias;
 use noir::expander::Expander;
 use noir::expression::RawQuery;
+use noir::implication::{Implication, Implications};
+use noir::saved_search::SavedSearch;
+use noir::synonym::Normalizer;
 
 
 fn r(expression: &str) -> RawQuery {
@@ -94,3 +97,96 @@ fn test_nonrecursive() {
         e.expand_str("begin hoge end").unwrap(),
         r("begin fuga end"));
 }
+
+#[test]
+fn test_implied_tags() {
+    let implications: Implications = [
+        Implication { child: "cat".to_owned(), parent: "animal".to_owned() },
+        Implication { child: "kitten".to_owned(), parent: "cat".to_owned() },
+    ].iter().collect();
+    let e = Expander::new(hashmap!{}, hashmap!{}).with_implications(implications);
+
+    assert_eq!(
+        e.expand_str("#animal").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag IN ('animal', 'cat', 'kitten')))"));
+    assert_eq!(
+        e.expand_str("#kitten").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag = 'kitten'))"));
+}
+
+#[test]
+fn test_normalized_tags() {
+    let mut normalizer = Normalizer::new(true, false);
+    normalizer.add_synonym("grey", "gray");
+    let e = Expander::new(hashmap!{}, hashmap!{}).with_normalizer(normalizer);
+
+    assert_eq!(
+        e.expand_str("#Grey").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag = 'gray'))"));
+}
+
+#[test]
+fn test_namespaced_tags() {
+    let e = Expander::new(hashmap!{}, hashmap!{});
+
+    assert_eq!(
+        e.expand_str("#artist:*").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE namespace = 'artist'))"));
+    assert_eq!(
+        e.expand_str("#rating:>=3").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE namespace = 'rating' AND tag_number(substr(tag, 8)) >= 3))"));
+    assert_eq!(
+        e.expand_str("#artist:foo*").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE namespace = 'artist' AND match('artist:foo*', tag)))"));
+    assert_eq!(
+        e.expand_str("#artist:foo").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag = 'artist:foo'))"));
+}
+
+#[test]
+fn test_tag_sources() {
+    let e = Expander::new(hashmap!{}, hashmap!{})
+        .with_sources(hashset!{"manual".to_owned(), "auto".to_owned()});
+
+    assert_eq!(
+        e.expand_str("#cat@manual").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag = 'cat' AND source = 'manual'))"));
+    assert_eq!(
+        e.expand_str("#cat@!auto").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag = 'cat' AND source != 'auto'))"));
+    assert_eq!(
+        e.expand_str("#artist:*@manual").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE namespace = 'artist' AND source = 'manual'))"));
+    assert_eq!(
+        e.expand_str("#user@example").unwrap(),
+        r("(path in (SELECT path FROM tags WHERE tag = 'user@example'))"));
+}
+
+#[test]
+fn test_in_collection() {
+    let e = Expander::new(hashmap!{}, hashmap!{});
+
+    assert_eq!(
+        e.expand_str("in_collection('deck') AND #cat").unwrap(),
+        r("(path in (SELECT path FROM collection_items WHERE collection = 'deck')) AND (path in (SELECT path FROM tags WHERE tag = 'cat'))"));
+}
+
+#[test]
+fn test_saved_search() {
+    let saved = SavedSearch {
+        name: "best".to_owned(),
+        expression: "#cat".to_owned(),
+        sort: Some("views DESC".to_owned()),
+        limit: Some(10),
+        ..Default::default()
+    };
+    let e = Expander::new(hashmap!{}, hashmap!{}).with_saved_searches(hashmap!{ "best".to_owned() => saved });
+
+    assert_eq!(
+        e.expand_str("@best AND width > 100").unwrap(),
+        r("(path in (SELECT path FROM images WHERE ((path in (SELECT path FROM tags WHERE tag = 'cat'))) ORDER BY views DESC LIMIT 10)) AND width > 100"));
+    assert_eq!(
+        e.expand_saved_search("best").unwrap(),
+        "((path in (SELECT path FROM tags WHERE tag = 'cat'))) ORDER BY views DESC LIMIT 10");
+    assert!(e.expand_str("@unknown").is_err());
+}
@@ -0,0 +1,76 @@
+use std::fs;
+
+use noir::database::Database;
+use noir::errors::AppError;
+use noir::federation::{self, Attached, FederatedMeta, MAIN};
+use noir::meta::Meta;
+use noir::output_format::OutputFormat;
+
+mod common;
+use common::temp_dir;
+
+
+
+fn meta() -> Meta {
+    common::meta("/a.png", 1)
+}
+
+fn write_federated(format: OutputFormat) -> String {
+    let mut out = vec![];
+    format.write_federated(&mut out, &FederatedMeta { origin: "work".to_owned(), meta: meta() }).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+
+#[test]
+fn test_federated_meta() {
+    let value = serde_json::to_value(&FederatedMeta { origin: "work".to_owned(), meta: meta() }).unwrap();
+    assert_eq!(value["origin"], "work");
+    assert_eq!(value["file"]["path"], "/a.png");
+    assert_eq!(value["format"], "png");
+}
+
+#[test]
+fn test_write_federated() {
+    assert_eq!(write_federated(OutputFormat::Simple), "work\t/a.png\n");
+    assert_eq!(
+        write_federated(OutputFormat::Chrysoberyl),
+        "@push-image --meta origin=work --meta width=10 --meta height=20 --meta format=png /a.png\n");
+}
+
+#[test]
+fn test_open_read_only() {
+    let dir = temp_dir("read-only");
+    let file = dir.join("db.sqlite");
+
+    assert!(Database::open_read_only(&file).is_err());
+    assert!(!file.exists());
+
+    Database::open(&file).unwrap().upsert(&meta()).unwrap();
+    let db = Database::open_read_only(&file).unwrap();
+    assert!(db.path_exists("/a.png").unwrap());
+    assert!(db.upsert(&meta()).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_database_of_origin() {
+    let dir = temp_dir("origin");
+    let main = Database::open(&dir.join("main.sqlite")).unwrap();
+    let work = Database::open(&dir.join("work.sqlite")).unwrap();
+    work.upsert(&meta()).unwrap();
+    let attached = vec![Attached { name: "work".to_owned(), db: work }];
+
+    let exists = |origin| federation::database(&main, &attached, origin).unwrap().path_exists("/a.png").unwrap();
+    assert!(!exists(None));
+    assert!(!exists(Some(MAIN)));
+    assert!(exists(Some("work")));
+    match federation::database(&main, &attached, Some("home")) {
+        Err(AppError::AttachedDatabaseNotFound(it)) => assert_eq!(it, "home"),
+        Err(it) => panic!("Unexpected: {:?}", it),
+        Ok(_) => panic!("Unexpected: Ok"),
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
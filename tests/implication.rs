@@ -0,0 +1,86 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::str::FromStr;
+
+use noir::implication::{Implication, Implications};
+use noir::tag::Tag;
+
+mod common;
+use common::{meta, open};
+
+
+
+fn i(child: &str, parent: &str) -> Implication {
+    Implication { child: child.to_owned(), parent: parent.to_owned() }
+}
+
+fn set(tags: &[&str]) -> BTreeSet<String> {
+    tags.iter().map(|it| it.to_string()).collect()
+}
+
+fn tag(tag: &str) -> Tag {
+    Tag::from_str(tag).unwrap()
+}
+
+fn pair(tag: &str, source: &str) -> (String, String) {
+    (tag.to_owned(), source.to_owned())
+}
+
+fn implications() -> Implications {
+    [i("cat", "animal"), i("dog", "animal"), i("kitten", "cat"), i("character:foo", "series:bar")].iter().collect()
+}
+
+
+#[test]
+fn test_descendants() {
+    let imps = implications();
+    assert_eq!(imps.descendants("animal"), set(&["animal", "cat", "dog", "kitten"]));
+    assert_eq!(imps.descendants("cat"), set(&["cat", "kitten"]));
+    assert_eq!(imps.descendants("series:bar"), set(&["series:bar", "character:foo"]));
+    assert_eq!(imps.descendants("unknown"), set(&["unknown"]));
+}
+
+#[test]
+fn test_ancestors() {
+    let imps = implications();
+    assert_eq!(imps.ancestors("kitten"), set(&["kitten", "cat", "animal"]));
+    assert_eq!(imps.ancestors("animal"), set(&["animal"]));
+}
+
+#[test]
+fn test_would_cycle() {
+    let imps = implications();
+    assert!(imps.would_cycle("animal", "kitten"));
+    assert!(imps.would_cycle("cat", "kitten"));
+    assert!(!imps.would_cycle("kitten", "animal"));
+    assert!(!imps.would_cycle("dog", "cat"));
+}
+
+#[test]
+fn test_apply_implications() {
+    let (dir, db) = open("apply-implications");
+    for path in &["/a.png", "/b.png", "/c.png"] {
+        db.upsert(&meta(path, 1)).unwrap();
+    }
+    db.set_source_priority("manual", 10).unwrap();
+    db.add_tags("/a.png", &[tag("kitten")], "manual").unwrap();
+    db.add_tags("/b.png", &[tag("cat")], "manual").unwrap();
+    db.add_tags("/c.png", &[tag("cat"), tag("animal")], "manual").unwrap();
+    db.delete_tags("/c.png", &[tag("animal")], "manual").unwrap();
+
+    db.add_implication(&tag("kitten"), &tag("cat")).unwrap();
+    db.add_implication(&tag("cat"), &tag("beast")).unwrap();
+    db.add_synonym(&tag("beast"), &tag("animal")).unwrap();
+
+    assert_eq!(db.apply_implications("implication").unwrap(), 3);
+    assert_eq!(
+        db.sourced_tags_by_path("/a.png").unwrap(),
+        vec![pair("animal", "implication"), pair("cat", "implication"), pair("kitten", "manual")]);
+    assert_eq!(db.sourced_tags_by_path("/b.png").unwrap(), vec![pair("animal", "implication"), pair("cat", "manual")]);
+    // Removed by the source with higher priority
+    assert_eq!(db.sourced_tags_by_path("/c.png").unwrap(), vec![pair("cat", "manual")]);
+
+    assert_eq!(db.apply_implications("implication").unwrap(), 0);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,51 @@
+
+use std::path::PathBuf;
+
+use noir::server::listen::Listen;
+
+
+#[test]
+fn test_parse() {
+    assert_eq!(Listen::parse("127.0.0.1", 9696).unwrap(), Listen::Tcp("127.0.0.1:9696".to_owned()));
+    assert_eq!(Listen::parse("127.0.0.1:8080", 9696).unwrap(), Listen::Tcp("127.0.0.1:8080".to_owned()));
+    assert_eq!(Listen::parse("::1", 9696).unwrap(), Listen::Tcp("[::1]:9696".to_owned()));
+    assert_eq!(Listen::parse("[::1]", 9696).unwrap(), Listen::Tcp("[::1]:9696".to_owned()));
+    assert_eq!(Listen::parse("[::1]:8080", 9696).unwrap(), Listen::Tcp("[::1]:8080".to_owned()));
+    assert_eq!(Listen::parse("localhost", 9696).unwrap(), Listen::Tcp("localhost:9696".to_owned()));
+    assert_eq!(Listen::parse("localhost:8080", 9696).unwrap(), Listen::Tcp("localhost:8080".to_owned()));
+    assert_eq!(Listen::parse("unix:/tmp/noir.sock", 9696).unwrap(), Listen::Unix(PathBuf::from("/tmp/noir.sock")));
+
+    assert!(Listen::parse("unix:", 9696).is_err());
+    assert!(Listen::parse("localhost:http", 9696).is_err());
+    assert!(Listen::parse("", 9696).is_err());
+}
+
+#[test]
+fn test_is_local() {
+    assert!(Listen::parse("127.0.0.1", 9696).unwrap().is_local());
+    assert!(Listen::parse("::1", 9696).unwrap().is_local());
+    assert!(Listen::parse("localhost:8080", 9696).unwrap().is_local());
+    assert!(Listen::parse("unix:/tmp/noir.sock", 9696).unwrap().is_local());
+
+    assert!(!Listen::parse("0.0.0.0", 9696).unwrap().is_local());
+    assert!(!Listen::parse("192.168.0.1:8080", 9696).unwrap().is_local());
+    assert!(!Listen::parse("example.com", 9696).unwrap().is_local());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_remove_stale_socket() {
+    use std::os::unix::net::UnixListener;
+    use noir::server::listen::remove_stale_socket;
+
+    let path = std::env::temp_dir().join(format!("noir-test-socket-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let listener = UnixListener::bind(&path).unwrap();
+    remove_stale_socket(&path).unwrap();
+    assert!(path.exists());
+
+    drop(listener);
+    remove_stale_socket(&path).unwrap();
+    assert!(!path.exists());
+}
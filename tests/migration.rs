@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use noir::database::Database;
+use noir::errors::AppError;
+use noir::migration::{MIGRATIONS, backup_path, latest_version, pending};
+
+mod common;
+
+
+
+#[test]
+fn test_versions() {
+    for (it, next) in MIGRATIONS.iter().zip(MIGRATIONS.iter().skip(1)) {
+        assert!(it.version < next.version);
+    }
+    assert_eq!(latest_version(), MIGRATIONS.last().unwrap().version);
+}
+
+#[test]
+fn test_pending() {
+    assert_eq!(pending(0).count(), MIGRATIONS.len());
+    assert_eq!(pending(latest_version()).count(), 0);
+    assert!(pending(1).all(|it| 1 < it.version));
+}
+
+#[test]
+fn test_backup_path() {
+    assert_eq!(
+        backup_path(Path::new("/db/default.sqlite"), 3),
+        Path::new("/db/default.sqlite.v3.bak"));
+}
+
+#[test]
+fn test_destructive_migration() {
+    let dir = common::temp_dir("destructive-migration");
+    let file = dir.join("db.sqlite");
+
+    // Before the ratings (v4)
+    Connection::open(&file).unwrap().execute(
+        "CREATE TABLE images (path TEXT PRIMARY KEY, width INTEGER, height INTEGER, ratio_width INTEGER, ratio_height INTEGER, format TEXT, animation BOOLEAN, file_size INTEGER, dhash TEXT, created TEXT, modified TEXT, accessed TEXT)",
+        []).unwrap();
+
+    match Database::open(&file) {
+        Err(AppError::DestructiveMigration(version, _)) => assert_eq!(version, 4),
+        Err(err) => panic!("Unexpected: {}", err),
+        Ok(_) => panic!("Unexpected: Ok"),
+    }
+
+    let db = Database::migrate_and_open(&file).unwrap();
+    assert_eq!(db.schema_version().unwrap(), latest_version());
+    assert!(backup_path(&file, 0).is_file());
+    drop(db);
+    Database::open(&file).unwrap();
+
+    let _ = fs::remove_dir_all(&dir);
+}
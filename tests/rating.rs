@@ -0,0 +1,18 @@
+use noir::rating::{from_favorite, validate};
+
+
+
+#[test]
+fn test_validate() {
+    assert_eq!(validate(0).unwrap(), 0);
+    assert_eq!(validate(5).unwrap(), 5);
+    assert!(validate(6).is_err());
+}
+
+#[test]
+fn test_from_favorite() {
+    assert_eq!(from_favorite("like"), Some(5));
+    assert_eq!(from_favorite("neutral"), Some(3));
+    assert_eq!(from_favorite("dislike"), Some(1));
+    assert_eq!(from_favorite("cat"), None);
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::str::FromStr;
+
+use noir::archive::PrefixRewrite;
+use noir::database::Database;
+use noir::errors::AppError;
+use noir::tag::Tag;
+
+mod common;
+use common::{meta, open};
+
+
+
+fn add(db: &Database, path: &str) {
+    db.upsert(&meta(path, 1)).unwrap();
+    db.add_tags(path, &[Tag::from_str("cat").unwrap()], "manual").unwrap();
+}
+
+
+#[test]
+fn test_relocate() {
+    let (dir, db) = open("relocate");
+    let (old, new) = (dir.join("pics"), dir.join("mnt"));
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+    fs::create_dir_all(new).unwrap();
+    fs::write(format!("{}/a.png", new), b"").unwrap();
+
+    add(&db, &format!("{}/a.png", old));
+    add(&db, &format!("{}tures/b.png", old));
+
+    let rewrite = PrefixRewrite { from: format!("{}/", old), to: new.to_owned() };
+    assert_eq!(db.relocate(&rewrite, 10).unwrap(), 1);
+    assert!(db.path_exists(&format!("{}/a.png", new)).unwrap());
+    assert!(!db.path_exists(&format!("{}/a.png", old)).unwrap());
+    assert_eq!(db.tags_by_path(&format!("{}/a.png", new)).unwrap(), vec!["cat".to_owned()]);
+    // Not under `old`
+    assert!(db.path_exists(&format!("{}tures/b.png", old)).unwrap());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_relocate_rollback() {
+    let (dir, db) = open("relocate-rollback");
+    let (old, new) = (dir.join("pics"), dir.join("mnt"));
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+
+    add(&db, &format!("{}/a.png", old));
+
+    let rewrite = PrefixRewrite { from: old.to_owned(), to: new.to_owned() };
+    match db.relocate(&rewrite, 10) {
+        Err(AppError::RelocatedFileNotFound(path)) => assert_eq!(path, format!("{}/a.png", new)),
+        it => panic!("Unexpected: {:?}", it),
+    }
+    assert!(db.path_exists(&format!("{}/a.png", old)).unwrap());
+    assert!(!db.path_exists(&format!("{}/a.png", new)).unwrap());
+    assert_eq!(db.tags_by_path(&format!("{}/a.png", old)).unwrap(), vec!["cat".to_owned()]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_relocate_root() {
+    let (dir, db) = open("relocate-root");
+    let (old, new) = (dir.join("pics"), dir.join("mnt"));
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+    fs::create_dir_all(format!("{}/cats", new)).unwrap();
+    fs::write(format!("{}/cats/a.png", new), b"").unwrap();
+
+    add(&db, &format!("{}/cats/a.png", old));
+    db.add_root("cats", &format!("{}/cats/", old)).unwrap();
+
+    let rewrite = PrefixRewrite { from: old.to_owned(), to: new.to_owned() };
+    assert_eq!(db.relocate(&rewrite, 10).unwrap(), 1);
+    let roots = db.roots().unwrap();
+    assert_eq!(roots[0].path, format!("{}/cats/", new));
+    assert_eq!(roots[0].images, 1);
+
+    let _ = fs::remove_dir_all(&dir);
+}
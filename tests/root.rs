@@ -0,0 +1,86 @@
+use std::fs;
+use std::str::FromStr;
+
+use noir::database::Database;
+use noir::errors::AppError;
+use noir::tag::Tag;
+
+mod common;
+use common::{meta, open};
+
+
+
+fn counts(db: &Database) -> Vec<(String, u64, u64)> {
+    db.roots().unwrap().into_iter().map(|it| (it.name, it.images, it.size)).collect()
+}
+
+
+#[test]
+fn test_add_and_delete() {
+    let (dir, db) = open("root-add");
+
+    db.upsert(&meta("/pics/a.png", 1)).unwrap();
+    db.upsert(&meta("/pics/cats/b.png", 2)).unwrap();
+    db.upsert(&meta("/pictures/c.png", 4)).unwrap();
+
+    db.add_root("pics", "/pics/").unwrap();
+    assert_eq!(counts(&db), vec![("pics".to_owned(), 2, 3)]);
+
+    db.add_root("cats", "/pics/cats").unwrap();
+    db.upsert(&meta("/pics/cats/d.png", 8)).unwrap();
+    assert_eq!(counts(&db), vec![("cats".to_owned(), 2, 10), ("pics".to_owned(), 1, 1)]);
+
+    assert!(db.delete_root("cats").unwrap());
+    assert!(!db.delete_root("cats").unwrap());
+    assert_eq!(counts(&db), vec![("pics".to_owned(), 3, 11)]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_move() {
+    let (dir, db) = open("root-move");
+    let (old, new) = (dir.join("pics"), dir.join("mnt"));
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+    fs::create_dir_all(format!("{}/cats", new)).unwrap();
+    fs::write(format!("{}/cats/a.png", new), b"").unwrap();
+
+    let path = format!("{}/cats/a.png", old);
+    db.upsert(&meta(&path, 1)).unwrap();
+    db.add_tags(&path, &[Tag::from_str("cat").unwrap()], "manual").unwrap();
+    db.add_root("pics", old).unwrap();
+
+    assert_eq!(db.move_root("pics", &format!("{}/", new), 10).unwrap(), 1);
+    let path = format!("{}/cats/a.png", new);
+    assert_eq!(db.get(&path).unwrap().map(|it| it.file.path), Some(path.clone()));
+    assert_eq!(db.tags_by_path(&path).unwrap(), vec!["cat".to_owned()]);
+    assert_eq!(db.roots().unwrap()[0].path, format!("{}/", new));
+    assert_eq!(counts(&db), vec![("pics".to_owned(), 1, 1)]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_move_rollback() {
+    let (dir, db) = open("root-move-rollback");
+    let (old, new) = (dir.join("pics"), dir.join("mnt"));
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+
+    let path = format!("{}/a.png", old);
+    db.upsert(&meta(&path, 1)).unwrap();
+    db.add_root("pics", old).unwrap();
+
+    match db.move_root("pics", new, 10) {
+        Err(AppError::RelocatedFileNotFound(it)) => assert_eq!(it, format!("{}/a.png", new)),
+        it => panic!("Unexpected: {:?}", it),
+    }
+    assert!(db.path_exists(&path).unwrap());
+    assert_eq!(db.roots().unwrap()[0].path, old);
+
+    match db.move_root("nothing", new, 10) {
+        Err(AppError::RootNotFound(it)) => assert_eq!(it, "nothing"),
+        it => panic!("Unexpected: {:?}", it),
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,14 @@
+use chrono::{Duration, Utc};
+
+use noir::search_history::older_than;
+
+
+
+#[test]
+fn test_older_than() {
+    let before = older_than("30 days").unwrap();
+    let expected = Utc::now() - Duration::days(30);
+    assert!((expected - before).num_seconds().abs() < 5);
+
+    assert!(older_than("cat").is_err());
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::str::FromStr;
+
+use noir::database::CASE_FOLDING;
+use noir::errors::AppError;
+use noir::synonym::Normalizer;
+use noir::tag::Tag;
+
+mod common;
+use common::{meta, open};
+
+
+
+fn tag(tag: &str) -> Tag {
+    Tag::from_str(tag).unwrap()
+}
+
+fn pair(tag: &str, source: &str) -> (String, String) {
+    (tag.to_owned(), source.to_owned())
+}
+
+
+#[test]
+fn test_synonyms() {
+    let mut n = Normalizer::new(false, false);
+    n.add_synonym("grey", "gray");
+    assert_eq!(n.normalize("grey"), "gray");
+    assert_eq!(n.normalize("gray"), "gray");
+    assert_eq!(n.normalize("Grey"), "Grey");
+}
+
+#[test]
+fn test_chain() {
+    let mut n = Normalizer::new(false, false);
+    n.add_synonym("grey", "gray");
+    n.add_synonym("gray", "ash");
+    assert_eq!(n.normalize("grey"), "ash");
+    assert_eq!(n.normalize("gray"), "ash");
+}
+
+#[test]
+fn test_would_cycle() {
+    let mut n = Normalizer::new(true, false);
+    n.add_synonym("grey", "gray");
+    n.add_synonym("gray", "ash");
+    assert!(n.would_cycle("ash", "grey"));
+    assert!(n.would_cycle("ash", "ASH"));
+    assert!(!n.would_cycle("silver", "grey"));
+    assert!(!n.would_cycle("grey", "silver"));
+}
+
+#[test]
+fn test_case_folding() {
+    let mut n = Normalizer::new(true, false);
+    n.add_synonym("Grey", "Gray");
+    assert_eq!(n.normalize("GREY"), "gray");
+    assert_eq!(n.normalize("Gray"), "gray");
+}
+
+#[test]
+fn test_unicode() {
+    let n = Normalizer::new(false, true);
+    assert_eq!(n.normalize("ｇｒａｙ"), "gray");
+    assert_eq!(n.normalize("ｶﾞ"), "ガ");
+
+    let n = Normalizer::new(false, false);
+    assert_eq!(n.normalize("ｇｒａｙ"), "ｇｒａｙ");
+}
+
+#[test]
+fn test_renormalize_tags() {
+    let (dir, db) = open("renormalize-tags");
+    db.upsert(&meta("/a.png", 1)).unwrap();
+    db.upsert(&meta("/b.png", 1)).unwrap();
+    db.add_tags("/a.png", &[tag("Grey"), tag("grey"), tag("gray")], "manual").unwrap();
+    db.add_tags("/b.png", &[tag("Grey")], "other").unwrap();
+
+    db.set_setting(CASE_FOLDING, true).unwrap();
+    db.add_synonym(&tag("grey"), &tag("gray")).unwrap();
+    match db.add_synonym(&tag("gray"), &tag("Grey")) {
+        Err(AppError::SynonymCycle(alias, canonical)) => assert_eq!((alias.as_str(), canonical.as_str()), ("gray", "Grey")),
+        it => panic!("Unexpected: {:?}", it),
+    }
+
+    assert_eq!(db.renormalize_tags().unwrap(), 3);
+    assert_eq!(db.sourced_tags_by_path("/a.png").unwrap(), vec![pair("gray", "manual")]);
+    assert_eq!(db.sourced_tags_by_path("/b.png").unwrap(), vec![pair("gray", "other")]);
+    assert_eq!(db.renormalize_tags().unwrap(), 0);
+
+    // New tags are normalized when added
+    db.add_tags("/b.png", &[tag("GREY")], "manual").unwrap();
+    assert_eq!(db.sourced_tags_by_path("/b.png").unwrap(), vec![pair("gray", "manual"), pair("gray", "other")]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
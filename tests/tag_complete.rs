@@ -0,0 +1,24 @@
+
+use std::collections::BTreeMap;
+
+use noir::tag_stats::{complete, TagCount};
+
+
+fn tc(tag: &str, count: u64) -> TagCount {
+    TagCount { tag: tag.to_owned(), count, sources: BTreeMap::new() }
+}
+
+fn tags(counts: Vec<TagCount>) -> Vec<String> {
+    counts.into_iter().map(|it| it.tag).collect()
+}
+
+
+#[test]
+fn test_complete() {
+    let counts = vec![tc("cat", 3), tc("catgirl", 10), tc("wildcat", 20), tc("crocodile_attack", 30), tc("dog", 100)];
+
+    assert_eq!(tags(complete(&counts, "cat", 10)), vec!["catgirl", "cat", "wildcat", "crocodile_attack"]);
+    assert_eq!(tags(complete(&counts, "CAT", 2)), vec!["catgirl", "cat"]);
+    assert_eq!(tags(complete(&counts, "dg", 10)), vec!["dog"]);
+    assert_eq!(tags(complete(&counts, "zzz", 10)), Vec::<String>::new());
+}
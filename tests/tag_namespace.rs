@@ -0,0 +1,72 @@
+use std::str::FromStr;
+
+use maplit::{btreemap, hashset};
+
+use noir::tag::{GroupedTags, SourceFilter, Tag, TagQuery, split_namespace, split_source, tag_number};
+
+
+
+#[test]
+fn test_split_namespace() {
+    assert_eq!(split_namespace("artist:foo"), (Some("artist"), "foo"));
+    assert_eq!(split_namespace("time:12:30"), (Some("time"), "12:30"));
+    assert_eq!(split_namespace("foo"), (None, "foo"));
+    assert_eq!(split_namespace(":foo"), (None, ":foo"));
+    assert_eq!(split_namespace("foo:"), (None, "foo:"));
+}
+
+#[test]
+fn test_tag_query() {
+    assert_eq!(TagQuery::parse("cat").unwrap(), TagQuery::Exact("cat"));
+    assert_eq!(TagQuery::parse("artist:foo").unwrap(), TagQuery::Exact("artist:foo"));
+    assert_eq!(TagQuery::parse("artist:*").unwrap(), TagQuery::Namespace("artist"));
+    assert_eq!(TagQuery::parse("artist:fo?*").unwrap(), TagQuery::Pattern { namespace: "artist", pattern: "artist:fo?*" });
+    assert_eq!(TagQuery::parse("rating:>=3").unwrap(), TagQuery::Compare { namespace: "rating", operator: ">=", value: 3.0 });
+    assert_eq!(TagQuery::parse("rating:<2.5").unwrap(), TagQuery::Compare { namespace: "rating", operator: "<", value: 2.5 });
+    assert_eq!(TagQuery::parse("rating:>high").unwrap(), TagQuery::Exact("rating:>high"));
+    assert!(TagQuery::parse("rating:>inf").is_err());
+    assert!(TagQuery::parse("rating:=NaN").is_err());
+}
+
+#[test]
+fn test_grouped_tags() {
+    let grouped: GroupedTags = vec!["cat", "artist:foo", "artist:bar", "rating:3"].into_iter().collect();
+    assert_eq!(grouped.tags, vec!["cat".to_owned()]);
+    assert_eq!(
+        grouped.namespaces,
+        btreemap!{
+            "artist".to_owned() => vec!["foo".to_owned(), "bar".to_owned()],
+            "rating".to_owned() => vec!["3".to_owned()],
+        });
+}
+
+#[test]
+fn test_split_source() {
+    let sources = hashset!{"manual".to_owned(), "auto".to_owned()};
+    assert_eq!(split_source("cat", &sources), ("cat", None));
+    assert_eq!(split_source("cat@manual", &sources), ("cat", Some(SourceFilter { negated: false, source: "manual" })));
+    assert_eq!(split_source("cat@!auto", &sources), ("cat", Some(SourceFilter { negated: true, source: "auto" })));
+    assert_eq!(split_source("mail@example@manual", &sources), ("mail@example", Some(SourceFilter { negated: false, source: "manual" })));
+    assert_eq!(split_source("user@example", &sources), ("user@example", None));
+    assert_eq!(split_source("cat@", &sources), ("cat@", None));
+    assert_eq!(split_source("@manual", &sources), ("@manual", None));
+}
+
+#[test]
+fn test_tag_name() {
+    assert!(Tag::from_str("cat").is_ok());
+    assert!(Tag::from_str("artist:foo").is_ok());
+    assert!(Tag::from_str("a b").is_err());
+    assert!(Tag::from_str(" cat").is_err());
+    assert!(Tag::from_str("cat\n").is_err());
+    assert!(Tag::from_str("").is_err());
+}
+
+#[test]
+fn test_tag_number() {
+    assert_eq!(tag_number("3"), Some(3.0));
+    assert_eq!(tag_number("-2.5"), Some(-2.5));
+    assert_eq!(tag_number("high"), None);
+    assert_eq!(tag_number("inf"), None);
+    assert_eq!(tag_number(""), None);
+}
@@ -0,0 +1,89 @@
+
+use std::fs;
+use std::str::FromStr;
+
+use noir::database::Database;
+use noir::errors::AppError;
+use noir::tag::{BatchResult, Tag, TagDiff, TagOperation};
+
+mod common;
+use common::{meta, open};
+
+
+fn tags(tags: &[&str]) -> Vec<Tag> {
+    tags.iter().map(|it| Tag::from_str(it).unwrap()).collect()
+}
+
+fn strings(strings: &[&str]) -> Vec<String> {
+    strings.iter().map(|it| it.to_string()).collect()
+}
+
+fn sorted_tags(db: &Database, path: &str) -> Vec<String> {
+    let mut tags = db.tags_by_path_and_source(path, "manual").unwrap();
+    tags.sort();
+    tags
+}
+
+
+#[test]
+fn test_add() {
+    assert_eq!(
+        TagOperation::Add.diff(&strings(&["cat", "dog"]), &tags(&["cat", "bird", "bird"])),
+        TagDiff { delete: vec![], insert: strings(&["bird"]) });
+}
+
+#[test]
+fn test_remove() {
+    assert_eq!(
+        TagOperation::Remove.diff(&strings(&["cat", "dog"]), &tags(&["cat", "bird"])),
+        TagDiff { delete: strings(&["cat"]), insert: vec![] });
+}
+
+#[test]
+fn test_set() {
+    assert_eq!(
+        TagOperation::Set.diff(&strings(&["cat", "dog"]), &tags(&["cat", "bird"])),
+        TagDiff { delete: strings(&["dog"]), insert: strings(&["bird"]) });
+    assert_eq!(
+        TagOperation::Set.diff(&strings(&["cat"]), &tags(&["cat"])),
+        TagDiff::default());
+}
+
+#[test]
+fn test_batch_tags() {
+    let (dir, db) = open("batch-tags");
+    let paths = strings(&["/a.png", "/b.png"]);
+    for path in &paths {
+        db.upsert(&meta(path, 1)).unwrap();
+    }
+    db.add_tags("/a.png", &tags(&["cat"]), "manual").unwrap();
+
+    let added = BatchResult { paths: 2, rows: 3 };
+    assert_eq!(db.batch_tags(TagOperation::Add, &paths, &tags(&["cat", "dog"]), "manual", true).unwrap(), added);
+    assert_eq!(sorted_tags(&db, "/a.png"), strings(&["cat"]));
+    assert_eq!(sorted_tags(&db, "/b.png"), strings(&[]));
+
+    assert_eq!(db.batch_tags(TagOperation::Add, &paths, &tags(&["cat", "dog"]), "manual", false).unwrap(), added);
+    assert_eq!(sorted_tags(&db, "/b.png"), strings(&["cat", "dog"]));
+    assert_eq!(db.batch_tags(TagOperation::Add, &paths, &tags(&["cat"]), "manual", false).unwrap(), BatchResult::default());
+
+    assert_eq!(
+        db.batch_tags(TagOperation::Remove, &paths[..1], &tags(&["cat"]), "manual", false).unwrap(),
+        BatchResult { paths: 1, rows: 1 });
+    assert_eq!(sorted_tags(&db, "/a.png"), strings(&["dog"]));
+
+    assert_eq!(
+        db.batch_tags(TagOperation::Set, &paths, &tags(&["dog", "bird"]), "manual", false).unwrap(),
+        BatchResult { paths: 2, rows: 3 });
+    assert_eq!(sorted_tags(&db, "/a.png"), strings(&["bird", "dog"]));
+    assert_eq!(sorted_tags(&db, "/b.png"), strings(&["bird", "dog"]));
+
+    let missing = strings(&["/a.png", "/missing.png"]);
+    match db.batch_tags(TagOperation::Set, &missing, &tags(&["fish"]), "manual", false) {
+        Err(AppError::PathNotFound(it)) => assert_eq!(it, "/missing.png"),
+        it => panic!("Unexpected: {:?}", it),
+    }
+    assert_eq!(sorted_tags(&db, "/a.png"), strings(&["bird", "dog"]));
+
+    let _ = fs::remove_dir_all(&dir);
+}
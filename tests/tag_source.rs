@@ -0,0 +1,59 @@
+use std::fs;
+use std::str::FromStr;
+
+use noir::tag::{Source, Tag};
+
+mod common;
+use common::{meta, open};
+
+
+
+fn tags(tags: &[&str]) -> Vec<Tag> {
+    tags.iter().map(|it| Tag::from_str(it).unwrap()).collect()
+}
+
+fn pair(tag: &str, source: &str) -> (String, String) {
+    (tag.to_owned(), source.to_owned())
+}
+
+fn source(name: &str, priority: i64, rows: u64) -> Source {
+    Source { name: name.to_owned(), priority, rows }
+}
+
+
+#[test]
+fn test_removals_and_priorities() {
+    let (dir, db) = open("tag-source");
+    db.upsert(&meta("/a.png", 1)).unwrap();
+    db.set_source_priority("manual", 10).unwrap();
+    db.set_source_priority("unused", 5).unwrap();
+
+    db.add_tags("/a.png", &tags(&["cat", "dog"]), "crawler").unwrap();
+    db.add_tags("/a.png", &tags(&["cat"]), "manual").unwrap();
+
+    // Removes the tags of the lower priority sources too, and keeps them removed
+    db.delete_tags("/a.png", &tags(&["cat"]), "manual").unwrap();
+    assert_eq!(db.sourced_tags_by_path("/a.png").unwrap(), vec![pair("dog", "crawler")]);
+    db.add_tags("/a.png", &tags(&["cat"]), "crawler").unwrap();
+    assert_eq!(db.sourced_tags_by_path("/a.png").unwrap(), vec![pair("dog", "crawler")]);
+
+    // The same priority does not override
+    db.delete_tags("/a.png", &tags(&["dog"]), "other").unwrap();
+    assert_eq!(db.sourced_tags_by_path("/a.png").unwrap(), vec![pair("dog", "crawler")]);
+
+    // The removing source itself can add it back
+    db.add_tags("/a.png", &tags(&["cat"]), "manual").unwrap();
+    assert_eq!(db.sourced_tags_by_path("/a.png").unwrap(), vec![pair("dog", "crawler"), pair("cat", "manual")]);
+
+    // Known only by the removal
+    db.add_tags("/a.png", &tags(&["bird"]), "cleaner").unwrap();
+    db.delete_tags("/a.png", &tags(&["bird"]), "cleaner").unwrap();
+
+    assert_eq!(db.source_priority("manual").unwrap(), 10);
+    assert_eq!(db.source_priority("other").unwrap(), 0);
+    assert_eq!(
+        db.sources().unwrap(),
+        vec![source("manual", 10, 1), source("unused", 5, 0), source("cleaner", 0, 0), source("crawler", 0, 1)]);
+
+    let _ = fs::remove_dir_all(&dir);
+}